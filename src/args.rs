@@ -1,14 +1,22 @@
 
-use std::fs;
-use std::io::Read;
-
+use crate::chess960;
 use crate::chess_structs::Color;
-use clap::{App, Arg, ArgMatches};
+use crate::variant::Variant;
+use clap::{App, Arg, ArgMatches, SubCommand};
 
 
 
-const ARG_FILE: &str = "file";
 const ARG_COLOR: &str = "color";
+const ARG_CHESS960: &str = "chess960";
+const ARG_VARIANT: &str = "variant";
+/// The names `Variant::from_name` knows
+const VARIANT_NAMES: &str = "'standard', 'threecheck', 'kingofthehill', 'antichess', 'crazyhouse', 'atomic', 'horde' or 'racingkings'";
+const CMD_PERFT: &str = "perft";
+const ARG_DEPTH: &str = "depth";
+const ARG_FEN: &str = "fen";
+const ARG_DIVIDE: &str = "divide";
+
+pub const INITIAL_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
 
 pub struct ExpectedActual<'a> {
@@ -21,31 +29,55 @@ pub enum ArgError<'a> {
     Invalid(&'a str, ExpectedActual<'a>)
 }
 
+pub struct PerftArgs {
+    pub depth: u32,
+    pub fen: String,
+    /// print the node count of each root move
+    pub divide: bool,
+}
+
 pub struct FryArgs {
     pub color: Color,
-    pub load_file: Option<String>
+    /// the Chess960 starting position to play from instead of the standard one
+    pub chess960: Option<usize>,
+    /// the rules to play and count perft by
+    pub variant: Variant,
+    /// count leaf nodes instead of playing, if given
+    pub perft: Option<PerftArgs>,
 }
 
 pub fn parse_args() -> Result<FryArgs, ArgError<'static>> {
     let args = App::new("fry chess engine")
         .version("0.1.0")
         .about("Wait, I'm having one of those things, you know, a headache with pictures.")
-        .arg(Arg::with_name(ARG_FILE)
-            .short("f")
-            .long("file")
-            .takes_value(true)
-            .help("path to .fen or .pgn file to load as starting point"))
         .arg(Arg::with_name(ARG_COLOR)
             .short("c")
             .long("color")
             .takes_value(true)
             .help("the color fry shall play as (w or b, default b)"))
+        .arg(Arg::with_name(ARG_CHESS960)
+            .long("chess960")
+            .takes_value(true)
+            .help("play Chess960 from the starting position with this number (0 to 959, 518 is the standard position)"))
+        .arg(Arg::with_name(ARG_VARIANT)
+            .long("variant")
+            .takes_value(true)
+            .help("the rules to play by: standard, threecheck, kingofthehill, antichess, crazyhouse, atomic, horde or racingkings (default standard)"))
+        .subcommand(SubCommand::with_name(CMD_PERFT)
+            .about("counts the positions reachable in <depth> plies, for verifying the move generator")
+            .arg(Arg::with_name(ARG_DEPTH)
+                .required(true)
+                .help("number of plies to search"))
+            .arg(Arg::with_name(ARG_FEN)
+                .long("fen")
+                .takes_value(true)
+                .help("position to count from, castling rights may be given as X-FEN or Shredder-FEN (default is the initial position of the variant)"))
+            .arg(Arg::with_name(ARG_DIVIDE)
+                .short("d")
+                .long("divide")
+                .help("show the node count below each root move")))
         .get_matches();
 
-    let load_file = args.value_of(ARG_FILE)
-        .map(|val| String::from(val))
-        .or(None);
-
     let color_str = args.value_of(ARG_COLOR).unwrap_or("b");
     let color = match color_str {
         "w" => Ok(Color::White),
@@ -53,8 +85,43 @@ pub fn parse_args() -> Result<FryArgs, ArgError<'static>> {
         _ => Err(ArgError::Invalid("Invalid value for color.", ExpectedActual { expected: "'w' or 'b'", actual: String::from(color_str) }))
     }?;
 
+    let chess960 = match args.value_of(ARG_CHESS960) {
+        Some(index_str) => Some(index_str.parse::<usize>()
+            .ok()
+            .filter(|&index| index < chess960::POSITIONS)
+            .ok_or_else(|| ArgError::Invalid("Invalid value for chess960.", ExpectedActual { expected: "a number from 0 to 959", actual: String::from(index_str) }))?),
+        None => None
+    };
+
+    let variant_str = args.value_of(ARG_VARIANT).unwrap_or("standard");
+    let variant = Variant::from_name(variant_str)
+        .ok_or_else(|| ArgError::Invalid("Invalid value for variant.", ExpectedActual { expected: VARIANT_NAMES, actual: String::from(variant_str) }))?;
+
+    let perft = match args.subcommand_matches(CMD_PERFT) {
+        Some(perft_args) => Some(parse_perft_args(perft_args, variant)?),
+        None => None
+    };
+
     return Ok(FryArgs {
         color,
-        load_file
+        chess960,
+        variant,
+        perft
+    });
+}
+
+fn parse_perft_args(args: &ArgMatches, variant: Variant) -> Result<PerftArgs, ArgError<'static>> {
+    let depth_str = args.value_of(ARG_DEPTH).unwrap_or("");
+    let depth = depth_str.parse::<u32>()
+        .map_err(|_| ArgError::Invalid("Invalid value for depth.", ExpectedActual { expected: "a non-negative integer", actual: String::from(depth_str) }))?;
+
+    let fen = args.value_of(ARG_FEN)
+        .map(|val| String::from(val))
+        .unwrap_or(String::from(variant.start_fen()));
+
+    return Ok(PerftArgs {
+        depth,
+        fen,
+        divide: args.is_present(ARG_DIVIDE)
     });
 }
@@ -0,0 +1,340 @@
+use std::sync::OnceLock;
+use crate::bitboard;
+use crate::chess_structs::{Board, Bitboard, Color, Kind, Square};
+
+/// Precomputed attack sets. Sliding pieces use fancy magic bitboards: the relevant blockers of a
+/// square are multiplied by a magic number, and the top bits of the product index a table shared by all squares.
+struct AttackTables {
+    rook_magics: [Magic; 64],
+    bishop_magics: [Magic; 64],
+    sliding: Vec<Bitboard>,
+    knight: [Bitboard; 64],
+    king: [Bitboard; 64],
+    /// Indexed by `[Color::index()][square]`
+    pawn: [[Bitboard; 64]; 2],
+    /// Indexed by two squares, see `between` and `line`
+    between: Vec<[Bitboard; 64]>,
+    line: Vec<[Bitboard; 64]>,
+}
+
+#[derive(Copy, Clone, Default)]
+struct Magic {
+    /// The squares whose occupancy matters, i.e. the rays without the board edge
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    /// Where this square's attack sets start in `AttackTables::sliding`
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: Bitboard) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+static TABLES: OnceLock<AttackTables> = OnceLock::new();
+
+fn tables() -> &'static AttackTables {
+    TABLES.get_or_init(AttackTables::new)
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.sliding[tables.rook_magics[square].index(occupied)]
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    let tables = tables();
+    tables.sliding[tables.bishop_magics[square].index(occupied)]
+}
+
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    tables().knight[square]
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    tables().king[square]
+}
+
+/// The squares a pawn of the given color on `square` attacks
+pub fn pawn_attacks(square: Square, color: Color) -> Bitboard {
+    tables().pawn[color.index()][square]
+}
+
+/// The squares strictly between two squares on a common rank, file or diagonal, empty if there is no such line
+pub fn between(a: Square, b: Square) -> Bitboard {
+    tables().between[a][b]
+}
+
+/// The whole rank, file or diagonal through both squares, empty if there is none
+pub fn line(a: Square, b: Square) -> Bitboard {
+    tables().line[a][b]
+}
+
+impl Board {
+    /// The pieces of `color` that attack `square`, whether or not it holds a piece
+    pub fn attackers_of(&self, square: Square, color: Color) -> Bitboard {
+        attackers_with(self, square, color, self.occupied)
+    }
+
+    /// Every square the pieces of `color` attack, i.e. the squares that side controls.
+    /// Pawns count with the squares they capture on, not the ones they push to.
+    pub fn attack_map(&self, color: Color) -> Bitboard {
+        let pieces = &self.pieces[color.index()];
+        let pawns = pieces[Kind::Pawn.index()];
+        let mut map = match color {
+            Color::White => bitboard::north_east(pawns) | bitboard::north_west(pawns),
+            Color::Black => bitboard::south_east(pawns) | bitboard::south_west(pawns),
+        };
+
+        let mut others = self.colors[color.index()] & !pawns;
+        while let Some(square) = bitboard::pop_lsb(&mut others) {
+            let bit = bitboard::bit(square);
+            map |= if pieces[Kind::Knight.index()] & bit != 0 {
+                knight_attacks(square)
+            } else if pieces[Kind::King.index()] & bit != 0 {
+                king_attacks(square)
+            } else if pieces[Kind::Bishop.index()] & bit != 0 {
+                bishop_attacks(square, self.occupied)
+            } else if pieces[Kind::Rook.index()] & bit != 0 {
+                rook_attacks(square, self.occupied)
+            } else {
+                queen_attacks(square, self.occupied)
+            };
+        }
+        map
+    }
+}
+
+/// Like `Board::attackers_of`, but with the sliders blocked by `occupied` instead of the pieces on the board
+pub fn attackers_with(board: &Board, square: Square, color: Color, occupied: Bitboard) -> Bitboard {
+    attackers_of_set(&board.pieces[color.index()], square, color, occupied)
+}
+
+/// The pieces among `pieces`, indexed by `Kind::index()` and of the given color, that attack `square`
+pub fn attackers_of_set(pieces: &[Bitboard; 6], square: Square, color: Color, occupied: Bitboard) -> Bitboard {
+    let diagonal = pieces[Kind::Bishop.index()] | pieces[Kind::Queen.index()];
+    let straight = pieces[Kind::Rook.index()] | pieces[Kind::Queen.index()];
+
+    // a pawn attacks this square from where an opposing pawn on this square would attack
+    (pawn_attacks(square, color.invert()) & pieces[Kind::Pawn.index()])
+        | (knight_attacks(square) & pieces[Kind::Knight.index()])
+        | (king_attacks(square) & pieces[Kind::King.index()])
+        | (bishop_attacks(square, occupied) & diagonal)
+        | (rook_attacks(square, occupied) & straight)
+}
+
+impl AttackTables {
+    fn new() -> AttackTables {
+        let mut tables = AttackTables {
+            rook_magics: [Magic::default(); 64],
+            bishop_magics: [Magic::default(); 64],
+            sliding: Vec::new(),
+            knight: [bitboard::EMPTY; 64],
+            king: [bitboard::EMPTY; 64],
+            pawn: [[bitboard::EMPTY; 64]; 2],
+            between: vec![[bitboard::EMPTY; 64]; 64],
+            line: vec![[bitboard::EMPTY; 64]; 64],
+        };
+
+        for square in 0..64 {
+            tables.knight[square] = bitboard::knight_attacks(square);
+            tables.king[square] = bitboard::king_attacks(square);
+            tables.pawn[Color::White.index()][square] = bitboard::pawn_attacks(square, Color::White);
+            tables.pawn[Color::Black.index()][square] = bitboard::pawn_attacks(square, Color::Black);
+        }
+
+        for a in 0..64 {
+            for b in 0..64 {
+                for &slow_attacks in [bitboard::rook_attacks, bitboard::bishop_attacks].iter() {
+                    if slow_attacks(a, bitboard::EMPTY) & bitboard::bit(b) != 0 {
+                        // what a slider on either square sees of the other one's rays, with the other one blocking
+                        tables.between[a][b] = slow_attacks(a, bitboard::bit(b)) & slow_attacks(b, bitboard::bit(a));
+                        tables.line[a][b] = (slow_attacks(a, bitboard::EMPTY) & slow_attacks(b, bitboard::EMPTY)) | bitboard::bit(a) | bitboard::bit(b);
+                    }
+                }
+            }
+        }
+
+        for square in 0..64 {
+            let mut rng = Rng(MAGIC_SEEDS[square / 8]);
+            tables.rook_magics[square] = find_magic(square, rook_mask(square), bitboard::rook_attacks, &mut tables.sliding, &mut rng);
+        }
+        for square in 0..64 {
+            let mut rng = Rng(MAGIC_SEEDS[square / 8]);
+            tables.bishop_magics[square] = find_magic(square, bishop_mask(square), bitboard::bishop_attacks, &mut tables.sliding, &mut rng);
+        }
+
+        tables
+    }
+}
+
+/// Random generator seeds per rank that are known to find magics after few attempts (the ones Stockfish uses).
+const MAGIC_SEEDS: [u64; 8] = [728, 10316, 55013, 32803, 12281, 15100, 16645, 255];
+
+/// Blockers on the last square of a ray make no difference, so the edges are left out of the masks.
+fn rook_mask(square: Square) -> Bitboard {
+    let own_rank = bitboard::RANK_1 << (square / 8 * 8);
+    let own_file = bitboard::FILE_A << (square % 8);
+    let edges = ((bitboard::RANK_1 | bitboard::RANK_8) & !own_rank) | ((bitboard::FILE_A | bitboard::FILE_H) & !own_file);
+    bitboard::rook_attacks(square, bitboard::EMPTY) & !edges
+}
+
+fn bishop_mask(square: Square) -> Bitboard {
+    let edges = bitboard::RANK_1 | bitboard::RANK_8 | bitboard::FILE_A | bitboard::FILE_H;
+    bitboard::bishop_attacks(square, bitboard::EMPTY) & !edges
+}
+
+/// Tries random sparse numbers until one maps every blocker configuration of `mask` to a slot
+/// without clashing with a different attack set, then appends the attack sets to `table`.
+fn find_magic(square: Square, mask: Bitboard, slow_attacks: fn(Square, Bitboard) -> Bitboard, table: &mut Vec<Bitboard>, rng: &mut Rng) -> Magic {
+    let bits = mask.count_ones();
+    let size = 1 << bits;
+
+    // walk all subsets of the mask (the Carry-Rippler trick)
+    let mut occupancies = Vec::with_capacity(size);
+    let mut occupied = bitboard::EMPTY;
+    loop {
+        occupancies.push(occupied);
+        occupied = occupied.wrapping_sub(mask) & mask;
+        if occupied == bitboard::EMPTY {
+            break;
+        }
+    }
+    let attacks: Vec<Bitboard> = occupancies.iter().map(|&occupied| slow_attacks(square, occupied)).collect();
+
+    let mut slots = vec![bitboard::EMPTY; size];
+    // the attempt each slot was last written in, so the slots need not be cleared between attempts
+    let mut written = vec![0; size];
+    let mut attempt = 0;
+    loop {
+        let magic = rng.next() & rng.next() & rng.next();
+        // magics that spread the mask over too few of the index bits rarely work
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+        attempt += 1;
+
+        let mut magic = Magic { mask, magic, shift: 64 - bits, offset: 0 };
+        let is_valid = occupancies.iter().zip(attacks.iter()).all(|(&occupied, &attack)| {
+            let index = magic.index(occupied);
+            if written[index] != attempt {
+                written[index] = attempt;
+                slots[index] = attack;
+                true
+            } else {
+                slots[index] == attack
+            }
+        });
+
+        if is_valid {
+            magic.offset = table.len();
+            table.extend_from_slice(&slots);
+            return magic;
+        }
+    }
+}
+
+/// xorshift64*, seeded so the same magics are found on every start
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+
+mod tests {
+    use crate::attacks;
+    use crate::bitboard;
+    use crate::chess_structs;
+    use crate::chess_structs::{Board, Color, Kind, Piece, square};
+
+    #[test]
+    fn sliding_attacks_match_ray_walk() {
+        let mut rng = attacks::Rng(1);
+        for square in 0..64 {
+            for _ in 0..200 {
+                // sparse occupancies, so the rays have some length to them
+                let occupied = rng.next() & rng.next();
+                assert_eq!(attacks::rook_attacks(square, occupied), bitboard::rook_attacks(square, occupied));
+                assert_eq!(attacks::bishop_attacks(square, occupied), bitboard::bishop_attacks(square, occupied));
+            }
+        }
+    }
+
+    #[test]
+    fn leaper_attacks_match() {
+        for square in 0..64 {
+            assert_eq!(attacks::knight_attacks(square), bitboard::knight_attacks(square));
+            assert_eq!(attacks::king_attacks(square), bitboard::king_attacks(square));
+            assert_eq!(attacks::pawn_attacks(square, Color::White), bitboard::pawn_attacks(square, Color::White));
+            assert_eq!(attacks::pawn_attacks(square, Color::Black), bitboard::pawn_attacks(square, Color::Black));
+        }
+    }
+
+    #[test]
+    fn attack_counts() {
+        // a knight in the corner has two squares, in the center eight
+        assert_eq!(attacks::knight_attacks(0).count_ones(), 2);
+        assert_eq!(attacks::knight_attacks(27).count_ones(), 8);
+        assert_eq!(attacks::king_attacks(0).count_ones(), 3);
+        // a rook on an empty board always sees 14 squares
+        assert!((0..64).all(|square| attacks::rook_attacks(square, bitboard::EMPTY).count_ones() == 14));
+        assert_eq!(attacks::bishop_attacks(27, bitboard::EMPTY).count_ones(), 13);
+        assert_eq!(attacks::pawn_attacks(8, Color::White).count_ones(), 1);
+    }
+
+    #[test]
+    fn between_and_line() {
+        // a1 and h8
+        assert_eq!(attacks::between(0, 63).count_ones(), 6);
+        assert_eq!(attacks::line(0, 63).count_ones(), 8);
+        assert_eq!(attacks::line(0, 63), attacks::line(9, 18));
+        // a1 and a2 are neighbours, a1 and b3 are not on a line
+        assert_eq!(attacks::between(0, 8), bitboard::EMPTY);
+        assert_eq!(attacks::line(0, 8), bitboard::FILE_A);
+        assert_eq!(attacks::between(0, 17), bitboard::EMPTY);
+        assert_eq!(attacks::line(0, 17), bitboard::EMPTY);
+        assert_eq!(attacks::between(4, 7), bitboard::bit(5) | bitboard::bit(6));
+    }
+
+    #[test]
+    fn attackers_of_test() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(3, 3), Piece { kind: Kind::Knight, color: Color::Black });
+        board.put_piece(square(2, 2), Piece { kind: Kind::Pawn, color: Color::White });
+        board.put_piece(square(3, 0), Piece { kind: Kind::Rook, color: Color::White });
+        board.put_piece(square(3, 1), Piece { kind: Kind::Queen, color: Color::White });
+        board.put_piece(square(5, 4), Piece { kind: Kind::Knight, color: Color::White });
+        board.put_piece(square(7, 7), Piece { kind: Kind::Bishop, color: Color::Black });
+
+        // the rook is behind the queen, so only the queen counts
+        let attackers = board.attackers_of(square(3, 3), Color::White);
+        assert_eq!(attackers, bitboard::bit(square(2, 2)) | bitboard::bit(square(3, 1)) | bitboard::bit(square(5, 4)));
+        // defending an own piece counts, but the knight blocks the bishop from going further
+        assert_eq!(board.attackers_of(square(3, 3), Color::Black), bitboard::bit(square(7, 7)));
+        assert_eq!(board.attackers_of(square(2, 2), Color::Black), bitboard::EMPTY);
+    }
+
+    #[test]
+    fn attack_map_test() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = crate::game_state::map_from_libfen(Color::Black, fen_rs::parse(fen).ok().unwrap()).board_state;
+        for &color in [Color::White, Color::Black].iter() {
+            let map = board.attack_map(color);
+            for square in 0..64 {
+                assert_eq!(map & bitboard::bit(square) != 0, board.attackers_of(square, color) != bitboard::EMPTY);
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::chess_structs::{Bitboard, Color, Square};
+
+pub const EMPTY: Bitboard = 0;
+
+pub const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+pub const FILE_H: Bitboard = FILE_A << 7;
+
+pub const RANK_1: Bitboard = 0xff;
+pub const RANK_2: Bitboard = RANK_1 << 8;
+pub const RANK_3: Bitboard = RANK_1 << 8 * 2;
+pub const RANK_6: Bitboard = RANK_1 << 8 * 5;
+pub const RANK_7: Bitboard = RANK_1 << 8 * 6;
+pub const RANK_8: Bitboard = RANK_1 << 8 * 7;
+
+/// d4, e4, d5 and e5
+pub const CENTER: Bitboard = (1 << 27) | (1 << 28) | (1 << 35) | (1 << 36);
+
+pub fn bit(square: Square) -> Bitboard {
+    1 << square
+}
+
+/// The lowest square in the set
+pub fn lsb(bitboard: Bitboard) -> Option<Square> {
+    if bitboard == EMPTY {
+        None
+    } else {
+        Some(bitboard.trailing_zeros() as Square)
+    }
+}
+
+/// The highest square in the set
+pub fn msb(bitboard: Bitboard) -> Option<Square> {
+    if bitboard == EMPTY {
+        None
+    } else {
+        Some(63 - bitboard.leading_zeros() as Square)
+    }
+}
+
+/// Removes the lowest square from the set and returns it
+pub fn pop_lsb(bitboard: &mut Bitboard) -> Option<Square> {
+    let square = lsb(*bitboard)?;
+    *bitboard &= *bitboard - 1;
+    Some(square)
+}
+
+// One step in each direction. Squares that would wrap around to the other side of the board are dropped.
+pub fn north(bitboard: Bitboard) -> Bitboard { bitboard << 8 }
+pub fn south(bitboard: Bitboard) -> Bitboard { bitboard >> 8 }
+pub fn east(bitboard: Bitboard) -> Bitboard { (bitboard & !FILE_H) << 1 }
+pub fn west(bitboard: Bitboard) -> Bitboard { (bitboard & !FILE_A) >> 1 }
+pub fn north_east(bitboard: Bitboard) -> Bitboard { (bitboard & !FILE_H) << 9 }
+pub fn north_west(bitboard: Bitboard) -> Bitboard { (bitboard & !FILE_A) << 7 }
+pub fn south_east(bitboard: Bitboard) -> Bitboard { (bitboard & !FILE_H) >> 7 }
+pub fn south_west(bitboard: Bitboard) -> Bitboard { (bitboard & !FILE_A) >> 9 }
+
+pub const ROOK_DIRECTIONS: [fn(Bitboard) -> Bitboard; 4] = [north, south, east, west];
+pub const BISHOP_DIRECTIONS: [fn(Bitboard) -> Bitboard; 4] = [north_east, north_west, south_east, south_west];
+
+// The attack functions below walk the board step by step. They are used to fill the lookup tables
+// in `attacks`, which is what move generation should use.
+
+/// Slides from `square` in each direction until the edge of the board or the first occupied square,
+/// which is included since it may hold a piece to capture.
+fn ray_attacks(square: Square, occupied: Bitboard, directions: &[fn(Bitboard) -> Bitboard]) -> Bitboard {
+    let mut attacks = EMPTY;
+    for step in directions {
+        let mut ray = step(bit(square));
+        while ray != EMPTY {
+            attacks |= ray;
+            if ray & occupied != EMPTY {
+                break;
+            }
+            ray = step(ray);
+        }
+    }
+    attacks
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    ray_attacks(square, occupied, &ROOK_DIRECTIONS)
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    ray_attacks(square, occupied, &BISHOP_DIRECTIONS)
+}
+
+pub fn knight_attacks(square: Square) -> Bitboard {
+    let knight = bit(square);
+    let one_file = east(knight) | west(knight);
+    let two_files = east(east(knight)) | west(west(knight));
+    north(north(one_file)) | south(south(one_file)) | north(two_files) | south(two_files)
+}
+
+pub fn king_attacks(square: Square) -> Bitboard {
+    let king = bit(square);
+    let rank = king | east(king) | west(king);
+    (rank | north(rank) | south(rank)) & !king
+}
+
+/// The squares a pawn of the given color on `square` attacks
+pub fn pawn_attacks(square: Square, color: Color) -> Bitboard {
+    let pawn = bit(square);
+    match color {
+        Color::White => north_east(pawn) | north_west(pawn),
+        Color::Black => south_east(pawn) | south_west(pawn),
+    }
+}
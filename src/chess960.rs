@@ -0,0 +1,62 @@
+use crate::chess_structs::{Color, Kind, Piece};
+
+/// The number of Chess960 starting positions
+pub const POSITIONS: usize = 960;
+
+/// Which two of the five squares left after placing the bishops and the queen the knights go on
+const KNIGHTS: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+/// The FEN of a Chess960 starting position, numbered from 0 to 959 the usual way, where 518 is the standard position.
+/// Castling rights are written as KQkq, which is unambiguous with one rook on either side of the king.
+pub fn start_position_fen(index: usize) -> String {
+    let mut back_rank = [None; 8];
+    let mut rest = index;
+    // one bishop on a light square, the b-, d-, f- or h-file, and one on a dark square
+    back_rank[rest % 4 * 2 + 1] = Some(Kind::Bishop);
+    rest /= 4;
+    back_rank[rest % 4 * 2] = Some(Kind::Bishop);
+    rest /= 4;
+    place(&mut back_rank, rest % 6, Kind::Queen);
+    rest /= 6;
+    // the second knight first, so the empty square the first one is counted on stays the same
+    let (first, second) = KNIGHTS[rest];
+    place(&mut back_rank, second, Kind::Knight);
+    place(&mut back_rank, first, Kind::Knight);
+    // the king between the rooks on what is left
+    for &kind in [Kind::Rook, Kind::King, Kind::Rook].iter() {
+        place(&mut back_rank, 0, kind);
+    }
+
+    let white: String = back_rank.iter()
+        .map(|kind| Piece { kind: kind.unwrap_or(Kind::Pawn), color: Color::White }.to_char())
+        .collect();
+    format!("{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1", white.to_lowercase(), white)
+}
+
+/// Puts a piece on the `nth` square of the back rank that is still empty
+fn place(back_rank: &mut [Option<Kind>; 8], nth: usize, kind: Kind) {
+    if let Some(square) = back_rank.iter_mut().filter(|square| square.is_none()).nth(nth) {
+        *square = Some(kind);
+    }
+}
+
+
+mod tests {
+    use crate::args;
+    use crate::chess960;
+
+    #[test]
+    fn start_position_test() {
+        assert_eq!(chess960::start_position_fen(518), args::INITIAL_FEN);
+        assert_eq!(chess960::start_position_fen(0), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!(chess960::start_position_fen(959), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1");
+    }
+
+    #[test]
+    fn positions_are_distinct() {
+        let mut fens: Vec<String> = (0..chess960::POSITIONS).map(chess960::start_position_fen).collect();
+        fens.sort();
+        fens.dedup();
+        assert_eq!(fens.len(), chess960::POSITIONS);
+    }
+}
@@ -1,8 +1,15 @@
 
-use std::ops;
+use std::fmt;
+use crate::bitboard;
+use crate::variant::Variant;
 
 pub type CastlingBitField = u32;
 pub type ChecksBitField = u8;
+pub type MoveFlags = u8;
+/// One bit per square, a1 is the lowest bit and h8 the highest
+pub type Bitboard = u64;
+/// Square index from 0 (a1) to 63 (h8), going along the ranks
+pub type Square = usize;
 
 pub const WHITE_KINGSIDE: CastlingBitField  = 1 << 0;
 pub const WHITE_QUEENSIDE: CastlingBitField = 1 << 1;
@@ -12,11 +19,21 @@ pub const BLACK_QUEENSIDE: CastlingBitField = 1 << 3;
 pub const CASTLING_UNAVAILABLE: CastlingBitField = 0;
 pub const CASTLING_FULL: CastlingBitField = WHITE_KINGSIDE | WHITE_QUEENSIDE | BLACK_KINGSIDE | BLACK_QUEENSIDE;
 
+/// The rooks castling rights belong to in standard chess: h1, a1, h8 and a8, in the order of the rights' bits
+pub const STANDARD_CASTLING_ROOKS: [Square; 4] = [7, 0, 63, 56];
+
 pub const WHITE_IS_CHECKED: ChecksBitField = 1 << 0;
 pub const BLACK_IS_CHECKED: ChecksBitField = 1 << 1;
 
 pub const NO_CHECKS: ChecksBitField = 0;
 
+pub const QUIET_MOVE: MoveFlags = 0;
+pub const CAPTURE: MoveFlags    = 1 << 0;
+pub const EN_PASSANT: MoveFlags = 1 << 1;
+pub const CASTLING: MoveFlags   = 1 << 2;
+/// Crazyhouse: a piece from the pocket is put on an empty square
+pub const DROP: MoveFlags       = 1 << 3;
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum Color {
     White,
@@ -29,6 +46,18 @@ impl Color {
             Color::Black => Color::White,
         }
     }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The width and height of the board
+pub const FILES: usize = 8;
+pub const RANKS: usize = 8;
+
+pub fn square(x: usize, y: usize) -> Square {
+    y * FILES + x
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -44,55 +73,122 @@ impl Index2D {
         }
     }
 
-    pub fn is_out_of_board(self) -> bool {
-        self.x > 7 || self.y > 7
+    pub fn from_square(square: Square) -> Index2D {
+        Index2D::new(square % FILES, square / FILES)
+    }
+
+    pub fn to_square(self) -> Square {
+        square(self.x, self.y)
     }
 }
-// TODO: remove checked add when thoroughly tested
-impl ops::AddAssign<&Vector2D> for Index2D {
-    fn add_assign(&mut self, rhs: &Vector2D) {
-        self.x = ((self.x as i64).checked_add(rhs.x)).unwrap() as usize;
-        self.y = ((self.y as i64).checked_add(rhs.y)).unwrap() as usize;
+/// Algebraic square name, e.g. e4
+impl fmt::Display for Index2D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.x as u8) as char, self.y + 1)
     }
 }
-// TODO: remove checked add when thoroughly tested
-impl ops::Add<Vector2D> for Index2D {
-    type Output = Option<Index2D>;
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Move {
+    pub from: Square,
+    /// For castling, the square of the rook the king castles with
+    pub to: Square,
+    /// The kind the pawn turns into, if this is a promotion, or the kind put down by a drop
+    pub promotion: Option<Kind>,
+    pub flags: MoveFlags,
+}
 
-    fn add(self, rhs: Vector2D) -> Self::Output {
-        let x = (self.x as i64).checked_add(rhs.x);
-        let y = (self.y as i64).checked_add(rhs.y);
-        if x.is_some() && y.is_some() {
-            Some(Index2D {
-                x: x.unwrap() as usize,
-                y: y.unwrap() as usize
-            })
-        } else {
-            None
+impl Move {
+    pub fn new(from: Square, to: Square, promotion: Option<Kind>, flags: MoveFlags) -> Move {
+        Move {
+            from,
+            to,
+            promotion,
+            flags
         }
     }
-}
 
-pub struct Vector2D {
-    pub x: i64,
-    pub y: i64,
+    /// A drop of a piece of the given kind from the pocket, `from` and `to` are both the square it goes to
+    pub fn drop(kind: Kind, to: Square) -> Move {
+        Move::new(to, to, Some(kind), DROP)
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.flags & CAPTURE > 0
+    }
+
+    pub fn is_en_passant(&self) -> bool {
+        self.flags & EN_PASSANT > 0
+    }
+
+    pub fn is_castling(&self) -> bool {
+        self.flags & CASTLING > 0
+    }
+
+    pub fn is_drop(&self) -> bool {
+        self.flags & DROP > 0
+    }
 }
-impl Vector2D {
-    pub fn new(x: i64, y: i64) -> Vector2D {
-        Vector2D {
-            x,
-            y
+
+/// Long algebraic notation, e.g. e2e4, e7e8q, e1g1 for castling.
+/// Chess960 castling that does not start from the standard squares is written as the king taking its own rook, e.g. b1a1,
+/// since the king's destination may be the square it stands on or one it could also step to.
+/// A crazyhouse drop is written as the piece and the square, e.g. N@f3.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(kind) = self.promotion.filter(|_| self.is_drop()) {
+            return write!(f, "{}@{}", Piece { kind, color: Color::White }.to_char(), Index2D::from_square(self.to));
+        }
+        let to = match (self.is_castling(), self.from % 8, self.to % 8) {
+            (true, 4, 7) => self.from + 2,
+            (true, 4, 0) => self.from - 2,
+            _ => self.to
+        };
+        write!(f, "{}{}", Index2D::from_square(self.from), Index2D::from_square(to))?;
+        if let Some(kind) = self.promotion {
+            write!(f, "{}", Piece { kind, color: Color::Black }.to_char())?;
         }
+        Ok(())
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// What `Board::make_move` changed besides moving the pieces, so `Board::unmake_move` can restore it
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Undo {
+    pub mv: Move,
+    pub captured: Option<Piece>,
+    pub en_passant: Option<Square>,
+    pub castling_availability: CastlingBitField,
+    pub checks: ChecksBitField,
+    pub check_counts: [u8; 2],
+    pub pockets: [[u8; 6]; 2],
+    pub promoted: Bitboard,
+    /// Atomic: the squares a capture blew up
+    pub exploded: Bitboard,
+    /// Atomic: the pieces that stood on the `exploded` squares, lowest square first
+    pub exploded_pieces: [Option<Piece>; 9],
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Board {
-    pub squares: [[Option<Piece>; 8]; 8],
+    /// Piece sets, indexed by `[Color::index()][Kind::index()]`
+    pub pieces: [[Bitboard; 6]; 2],
+    /// All pieces of each color, indexed by `Color::index()`
+    pub colors: [Bitboard; 2],
+    pub occupied: Bitboard,
     pub turn: Color,
-    pub en_passant: Option<Index2D>,
+    pub en_passant: Option<Square>,
     pub castling_availability: u32,
+    /// The square of the rook each castling right belongs to, indexed by the bit of the right.
+    /// Set once for a game, Chess960 starting positions have their rooks elsewhere than a1 and h1.
+    pub castling_rooks: [Square; 4],
     pub checks: u8,
+    pub variant: Variant,
+    /// The checks each side has given so far, indexed by `Color::index()`. Only counted in three-check.
+    pub check_counts: [u8; 2],
+    /// Crazyhouse: the captured pieces each side holds and may drop, indexed by `[Color::index()][Kind::index()]`
+    pub pockets: [[u8; 6]; 2],
+    /// Crazyhouse: the pieces that were promoted, which go into the pocket as pawns when captured
+    pub promoted: Bitboard,
 }
 
 impl Board {
@@ -101,7 +197,7 @@ impl Board {
             let rank= 7 - rank;
             print!(" {} | ", rank+1);
             for file in 0..8 {
-                let square = match self.squares[rank][file] {
+                let square = match self.piece_at(square(file, rank)) {
                     Some(piece) => piece.to_char(),
                     None => '.'
                 };
@@ -116,19 +212,78 @@ impl Board {
             print!(" {} ", c);
         }
         println!();
+
+        if self.variant == Variant::Crazyhouse {
+            let mut in_hand = String::new();
+            for &color in [Color::White, Color::Black].iter() {
+                for &kind in Kind::ALL.iter() {
+                    let piece = Piece { kind, color }.to_char();
+                    in_hand.extend(std::iter::repeat(piece).take(self.pockets[color.index()][kind.index()] as usize));
+                }
+            }
+            println!("     in hand: {}", if in_hand.is_empty() { "-" } else { &in_hand });
+        }
     }
 
-    pub fn new(turn: Color, en_passant: Option<Index2D>, castling_availability: CastlingBitField, checks: ChecksBitField) -> Board {
+    pub fn new(turn: Color, en_passant: Option<Square>, castling_availability: CastlingBitField, checks: ChecksBitField) -> Board {
         Board {
-            squares: [
-                [None; 8], [None; 8], [None; 8], [None; 8], [None; 8], [None; 8], [None; 8], [None; 8]
-            ],
+            pieces: [[bitboard::EMPTY; 6]; 2],
+            colors: [bitboard::EMPTY; 2],
+            occupied: bitboard::EMPTY,
             turn,
             en_passant,
             castling_availability,
-            checks
+            castling_rooks: STANDARD_CASTLING_ROOKS,
+            checks,
+            variant: Variant::Standard,
+            check_counts: [0; 2],
+            pockets: [[0; 6]; 2],
+            promoted: bitboard::EMPTY
         }
     }
+
+    /// The square of the rook the given castling right belongs to
+    pub fn castling_rook(&self, right: CastlingBitField) -> Square {
+        self.castling_rooks[right.trailing_zeros() as usize]
+    }
+
+    /// The squares holding pieces of the given color and kind
+    pub fn bitboard(&self, color: Color, kind: Kind) -> Bitboard {
+        self.pieces[color.index()][kind.index()]
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        let mask = bitboard::bit(square);
+        if self.occupied & mask == 0 {
+            return None;
+        }
+        let color = if self.colors[Color::White.index()] & mask != 0 { Color::White } else { Color::Black };
+        Kind::ALL.iter()
+            .find(|kind| self.pieces[color.index()][kind.index()] & mask != 0)
+            .map(|&kind| Piece { kind, color })
+    }
+
+    /// Puts a piece on a square, replacing whatever stood there
+    pub fn put_piece(&mut self, square: Square, piece: Piece) {
+        self.remove_piece(square);
+        let mask = bitboard::bit(square);
+        self.pieces[piece.color.index()][piece.kind.index()] |= mask;
+        self.colors[piece.color.index()] |= mask;
+        self.occupied |= mask;
+    }
+
+    pub fn remove_piece(&mut self, square: Square) -> Option<Piece> {
+        let piece = self.piece_at(square)?;
+        let mask = !bitboard::bit(square);
+        self.pieces[piece.color.index()][piece.kind.index()] &= mask;
+        self.colors[piece.color.index()] &= mask;
+        self.occupied &= mask;
+        Some(piece)
+    }
+
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        bitboard::lsb(self.bitboard(color, Kind::King))
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -142,6 +297,12 @@ pub enum Kind {
 }
 
 impl Kind {
+    pub const ALL: [Kind; 6] = [Kind::Pawn, Kind::Bishop, Kind::Knight, Kind::Rook, Kind::King, Kind::Queen];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
     pub fn value(&self) -> i32 {
         match self {
             Kind::Pawn => 100,
@@ -154,7 +315,7 @@ impl Kind {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Piece {
     pub kind: Kind,
     pub color: Color
@@ -1,24 +1,132 @@
 
-use crate::chess_structs::{Board, Color};
+use std::cmp;
+use crate::chess_structs::{Board, Color, Kind, Square};
+use crate::variant::Variant;
 
+/// Three-check: what having given no, one and two checks is worth. The third one wins the game.
+const CHECKS_GIVEN: [i32; 3] = [0, 250, 700];
+
+/// Antichess: what a king is worth, it is an ordinary piece there
+const ANTICHESS_KING: i32 = 300;
+
+/// Crazyhouse: what a piece in hand is worth, in percent of its value on the board.
+/// It can be dropped wherever it is needed.
+const IN_HAND: i32 = 110;
+
+/// King of the hill: what a king is worth by the number of king moves it needs to reach the center.
+/// The king on the center has won the game already.
+const HILL_DISTANCE: [i32; 4] = [0, 400, 150, 50];
+
+/// Racing kings: what a king is worth by the number of ranks it still has to go to the eighth.
+/// The king on the eighth rank has won the race already.
+const RACE_DISTANCE: [i32; 8] = [0, 500, 320, 200, 120, 70, 30, 0];
 
 pub fn eval(board: &Board) -> i32 {
-    let score: i32 = board.squares.iter()
-        .flat_map(|squares| squares.iter()
-            .map(|square| match square {
-                None => 0,
-                Some(piece) => match piece.color {
-                    Color::White => piece.kind.value(),
-                    Color::Black => -piece.kind.value()
-                }
-            })
-        )
+    if board.variant == Variant::Antichess {
+        return antichess_eval(board);
+    }
+
+    let score: i32 = Kind::ALL.iter()
+        .map(|&kind| {
+            let white = board.bitboard(Color::White, kind).count_ones() as i32;
+            let black = board.bitboard(Color::Black, kind).count_ones() as i32;
+            (white - black) * kind.value()
+        })
         .sum();
 
-    score
+    score + variant_eval(board)
+}
+
+/// Antichess is won by getting rid of the pieces, so the material counts for the other side
+fn antichess_eval(board: &Board) -> i32 {
+    Kind::ALL.iter()
+        .map(|&kind| {
+            let value = if kind == Kind::King { ANTICHESS_KING } else { kind.value() };
+            let white = board.bitboard(Color::White, kind).count_ones() as i32;
+            let black = board.bitboard(Color::Black, kind).count_ones() as i32;
+            (black - white) * value
+        })
+        .sum()
+}
+
+/// What counts towards the variant's own way of winning
+fn variant_eval(board: &Board) -> i32 {
+    match board.variant {
+        Variant::Standard | Variant::Antichess | Variant::Atomic => 0,
+        // the horde has no king to weigh up the black one
+        Variant::Horde => board.bitboard(Color::Black, Kind::King).count_ones() as i32 * Kind::King.value(),
+        Variant::Crazyhouse => Kind::ALL.iter()
+            .map(|&kind| {
+                let white = board.pockets[Color::White.index()][kind.index()] as i32;
+                let black = board.pockets[Color::Black.index()][kind.index()] as i32;
+                (white - black) * kind.value() * IN_HAND / 100
+            })
+            .sum(),
+        Variant::ThreeCheck => {
+            let checks_given = |color: Color| CHECKS_GIVEN[(board.check_counts[color.index()] as usize).min(CHECKS_GIVEN.len() - 1)];
+            checks_given(Color::White) - checks_given(Color::Black)
+        }
+        Variant::KingOfTheHill => {
+            let hill_distance = |color: Color| board.king_square(color)
+                .map_or(0, |king| HILL_DISTANCE[distance_to_center(king)]);
+            hill_distance(Color::White) - hill_distance(Color::Black)
+        }
+        Variant::RacingKings => {
+            let race_distance = |color: Color| board.king_square(color)
+                .map_or(0, |king| RACE_DISTANCE[7 - king / 8]);
+            race_distance(Color::White) - race_distance(Color::Black)
+        }
+    }
+}
+
+/// The number of king moves from `square` to the nearest of the four center squares
+fn distance_to_center(square: Square) -> usize {
+    let distance = |coordinate: usize| if coordinate < 3 { 3 - coordinate } else { coordinate.saturating_sub(4) };
+    cmp::max(distance(square % 8), distance(square / 8))
 }
 
 
 mod tests {
+    use crate::chess_structs::Color;
+    use crate::evaluator;
+    use crate::game_state;
+    use crate::variant::Variant;
 
-}
\ No newline at end of file
+    #[test]
+    fn three_check_test() {
+        let mut board = game_state::parse_fen(Color::Black, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 +2+1").ok().unwrap().board_state;
+        assert_eq!(evaluator::eval(&board), 0);
+        board.variant = Variant::ThreeCheck;
+        assert!(evaluator::eval(&board) > 0);
+    }
+
+    #[test]
+    fn king_of_the_hill_test() {
+        let eval_variant = |fen: &str| {
+            let mut board = game_state::parse_fen(Color::Black, fen).ok().unwrap().board_state;
+            board.variant = Variant::KingOfTheHill;
+            evaluator::eval(&board)
+        };
+        // one king a step closer to the center
+        let closer = eval_variant("7k/8/8/8/8/2K5/8/8 w - - 0 1");
+        let further = eval_variant("7k/8/8/8/8/8/1K6/8 w - - 0 1");
+        assert!(closer > further);
+        assert!(further > 0);
+    }
+
+    #[test]
+    fn antichess_test() {
+        let mut board = game_state::parse_fen(Color::Black, "4k3/8/8/8/8/8/8/3QK3 w - - 0 1").ok().unwrap().board_state;
+        assert!(evaluator::eval(&board) > 0);
+        board.variant = Variant::Antichess;
+        assert_eq!(evaluator::eval(&board), -900);
+    }
+
+    #[test]
+    fn pieces_in_hand_test() {
+        let mut board = game_state::parse_fen(Color::Black, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[Np] w KQkq - 0 1").ok().unwrap().board_state;
+        assert_eq!(evaluator::eval(&board), 0);
+        board.variant = Variant::Crazyhouse;
+        assert!(evaluator::eval(&board) > 300 - 100);
+    }
+}
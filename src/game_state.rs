@@ -1,6 +1,9 @@
+use crate::bitboard;
 use crate::chess_structs;
-use crate::chess_structs::{Color, Board, Index2D, Piece};
+use crate::generator;
+use crate::chess_structs::{Color, Board, Bitboard, Index2D, Piece, Kind, CastlingBitField, square, FILES, RANKS};
 use crate::libmappings::mappings_fenrs;
+use crate::variant;
 
 pub struct GameState {
     pub fry_color: Color,
@@ -25,7 +28,7 @@ pub fn map_from_libfen(fry_color: Color, game_state: fen_rs::GameState) -> GameS
 
     let active_color = mappings_fenrs::map_color(game_state.active_color);
     let en_passant = match game_state.en_passant {
-        Some(pos) => Some(mappings_fenrs::map_position(pos)),
+        Some(pos) => Some(mappings_fenrs::map_position(pos).to_square()),
         None => None
     };
 
@@ -39,11 +42,13 @@ pub fn map_from_libfen(fry_color: Color, game_state: fen_rs::GameState) -> GameS
         for piece in rank {
             if let Some(piece) = piece {
                 let pos = mappings_fenrs::map_position(piece.position);
-                board_state.squares[pos.y][pos.x] = Some(mappings_fenrs::map_piece(*piece));
+                board_state.put_piece(pos.to_square(), mappings_fenrs::map_piece(*piece));
             }
         }
     }
 
+    generator::update_checks(&mut board_state);
+
     let half_moves = game_state.half_move_clock;
 
     GameState {
@@ -53,3 +58,187 @@ pub fn map_from_libfen(fry_color: Color, game_state: fen_rs::GameState) -> GameS
         moves: vec![]
     }
 }
+
+/// Parses a FEN whose castling rights may also be given the Chess960 ways: X-FEN, where K and Q stand for
+/// the outermost rook on that side of the king, or Shredder-FEN, which names the files of the rooks, e.g. HAha.
+/// A three-check FEN may add the checks given, e.g. +1+0 at the end, or the checks remaining, e.g. 2+3 after the en passant square.
+/// A crazyhouse FEN may add the pieces in hand, see `parse_crazyhouse`.
+pub fn parse_fen(fry_color: Color, fen: &str) -> Result<GameState, fen_rs::Error> {
+    let mut fields: Vec<&str> = fen.split_whitespace().collect();
+    let (placement, pockets, promoted) = parse_crazyhouse(fields.first().copied().unwrap_or(""))?;
+    if let Some(field) = fields.first_mut() {
+        *field = &placement;
+    }
+    let check_counts = match fields.iter().skip(3).position(|field| field.contains('+')) {
+        Some(index) => parse_check_counts(fields.remove(index + 3))?,
+        None => [0; 2]
+    };
+    // fen_rs only knows KQkq with the rooks in the corners, so the castling field is read here
+    let castling = match fields.get_mut(2) {
+        Some(field) => std::mem::replace(field, "-"),
+        None => "-"
+    };
+
+    let mut game_state = map_from_libfen(fry_color, fen_rs::parse(&fields.join(" "))?);
+    set_castling_rights(&mut game_state.board_state, castling)?;
+    game_state.board_state.check_counts = check_counts;
+    game_state.board_state.pockets = pockets;
+    game_state.board_state.promoted = promoted;
+    Ok(game_state)
+}
+
+/// Splits the crazyhouse additions off the piece placement of a FEN: the pieces in hand, in brackets as in
+/// RNBQKBNR[Qp] or as a ninth rank as in RNBQKBNR/Qp, and a ~ after each promoted piece.
+/// Returns the placement without them, the pockets and the promoted pieces.
+fn parse_crazyhouse(placement: &str) -> Result<(String, [[u8; 6]; 2], Bitboard), fen_rs::Error> {
+    let invalid = || fen_rs::Error(format!("crazyhouse: {}", placement));
+    let (pieces, in_hand) = match placement.find('[') {
+        Some(index) => (&placement[..index], placement[index + 1..].strip_suffix(']').ok_or_else(invalid)?),
+        None if placement.matches('/').count() == 8 => placement.rsplit_once('/').ok_or_else(invalid)?,
+        None => (placement, "")
+    };
+
+    let mut pockets = [[0; 6]; 2];
+    for c in in_hand.chars().filter(|&c| c != '-') {
+        let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+        let kind = match c.to_ascii_lowercase() {
+            'p' => Kind::Pawn,
+            'n' => Kind::Knight,
+            'b' => Kind::Bishop,
+            'r' => Kind::Rook,
+            'q' => Kind::Queen,
+            _ => return Err(invalid())
+        };
+        pockets[color.index()][kind.index()] += 1;
+    }
+
+    let mut promoted = bitboard::EMPTY;
+    let (mut file, mut rank): (usize, usize) = (0, RANKS - 1);
+    let mut after_piece = false;
+    for c in pieces.chars() {
+        match c {
+            '/' => {
+                file = 0;
+                rank = rank.checked_sub(1).ok_or_else(invalid)?;
+            }
+            // a mark belongs to the piece right before it. fen_rs checks the ranks only later, after the marks are gone.
+            '~' if after_piece && file <= FILES => promoted |= bitboard::bit(square(file - 1, rank)),
+            '~' => return Err(invalid()),
+            _ => file += c.to_digit(10).unwrap_or(1) as usize
+        }
+        after_piece = c.is_ascii_alphabetic();
+    }
+
+    Ok((pieces.replace('~', ""), pockets, promoted))
+}
+
+/// Reads the three-check counters of a FEN into the checks given by white and black.
+/// They are either the checks given, as in +1+0, or the checks remaining, as in 2+3.
+fn parse_check_counts(field: &str) -> Result<[u8; 2], fen_rs::Error> {
+    let invalid = || fen_rs::Error(format!("checks: {}", field));
+    let counts: Vec<u8> = field.trim_start_matches('+')
+        .split('+')
+        .map(|count| count.parse::<u8>().ok().filter(|&count| count <= variant::CHECKS_TO_WIN))
+        .collect::<Option<Vec<u8>>>()
+        .filter(|counts| counts.len() == 2)
+        .ok_or_else(invalid)?;
+
+    if field.starts_with('+') {
+        Ok([counts[0], counts[1]])
+    } else {
+        Ok([variant::CHECKS_TO_WIN - counts[0], variant::CHECKS_TO_WIN - counts[1]])
+    }
+}
+
+/// Sets the castling rights, and the rooks they belong to, from the castling field of a FEN
+fn set_castling_rights(board: &mut Board, field: &str) -> Result<(), fen_rs::Error> {
+    board.castling_availability = chess_structs::CASTLING_UNAVAILABLE;
+    for c in field.chars().filter(|&c| c != '-') {
+        let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+        let rank = match color {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+        let invalid = || fen_rs::Error(format!("castling: no king and rook to castle with for {}", c));
+
+        let king = board.king_square(color).filter(|king| king / 8 == rank).ok_or_else(invalid)?;
+        let rooks = board.bitboard(color, Kind::Rook) & bitboard::RANK_1 << (8 * rank);
+        let above_king = !(bitboard::bit(king) | (bitboard::bit(king) - 1));
+        let rook = match c.to_ascii_lowercase() {
+            'k' => bitboard::msb(rooks & above_king),
+            'q' => bitboard::lsb(rooks & (bitboard::bit(king) - 1)),
+            file @ 'a'..='h' => Some(square(file as usize - 'a' as usize, rank)).filter(|&rook| rooks & bitboard::bit(rook) != 0),
+            _ => None
+        }.ok_or_else(invalid)?;
+
+        let right: CastlingBitField = match (color, rook > king) {
+            (Color::White, true) => chess_structs::WHITE_KINGSIDE,
+            (Color::White, false) => chess_structs::WHITE_QUEENSIDE,
+            (Color::Black, true) => chess_structs::BLACK_KINGSIDE,
+            (Color::Black, false) => chess_structs::BLACK_QUEENSIDE,
+        };
+        board.castling_availability |= right;
+        board.castling_rooks[right.trailing_zeros() as usize] = rook;
+    }
+    Ok(())
+}
+
+
+mod tests {
+    use crate::bitboard;
+    use crate::chess_structs;
+    use crate::chess_structs::{Color, Kind, square};
+    use crate::game_state;
+
+    #[test]
+    fn castling_field_test() {
+        let standard = game_state::parse_fen(Color::Black, "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").ok().unwrap().board_state;
+        assert_eq!(standard.castling_availability, chess_structs::CASTLING_FULL);
+        assert_eq!(standard.castling_rooks, chess_structs::STANDARD_CASTLING_ROOKS);
+
+        // X-FEN takes the outermost rook, Shredder-FEN names the file
+        let fen = "1r2k1rr/8/8/8/8/8/8/RR2K1R1 w Kb - 0 1";
+        let board = game_state::parse_fen(Color::Black, fen).ok().unwrap().board_state;
+        assert_eq!(board.castling_availability, chess_structs::WHITE_KINGSIDE | chess_structs::BLACK_QUEENSIDE);
+        assert_eq!(board.castling_rook(chess_structs::WHITE_KINGSIDE), square(6, 0));
+        assert_eq!(board.castling_rook(chess_structs::BLACK_QUEENSIDE), square(1, 7));
+
+        let shredder = game_state::parse_fen(Color::Black, "1r2k1rr/8/8/8/8/8/8/RR2K1R1 w GAgb - 0 1").ok().unwrap().board_state;
+        assert_eq!(shredder.castling_availability, chess_structs::CASTLING_FULL);
+        assert_eq!(shredder.castling_rook(chess_structs::WHITE_QUEENSIDE), square(0, 0));
+        assert_eq!(shredder.castling_rook(chess_structs::BLACK_KINGSIDE), square(6, 7));
+
+        // no rook on the c-file
+        assert!(game_state::parse_fen(Color::Black, "1r2k1rr/8/8/8/8/8/8/RR2K1R1 w C - 0 1").is_err());
+    }
+
+    #[test]
+    fn check_counts_test() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(game_state::parse_fen(Color::Black, fen).ok().unwrap().board_state.check_counts, [0, 0]);
+        let given = game_state::parse_fen(Color::Black, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 +2+1").ok().unwrap();
+        assert_eq!(given.board_state.check_counts, [2, 1]);
+        let remaining = game_state::parse_fen(Color::Black, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 1+2 0 1").ok().unwrap();
+        assert_eq!(remaining.board_state.check_counts, [2, 1]);
+        assert!(game_state::parse_fen(Color::Black, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 +4+0").is_err());
+    }
+
+    #[test]
+    fn pockets_test() {
+        let board = game_state::parse_fen(Color::Black, "4Q~2k/8/8/8/8/8/8/4K3[QNpp] w - - 0 1").ok().unwrap().board_state;
+        assert_eq!(board.pockets[Color::White.index()], [0, 0, 1, 0, 0, 1]);
+        assert_eq!(board.pockets[Color::Black.index()][Kind::Pawn.index()], 2);
+        assert_eq!(board.promoted, bitboard::bit(square(4, 7)));
+        assert_eq!(board.piece_at(square(4, 7)).map(|piece| piece.kind), Some(Kind::Queen));
+
+        let ninth_rank = game_state::parse_fen(Color::Black, "4Q~2k/8/8/8/8/8/8/4K3/QNpp w - - 0 1").ok().unwrap().board_state;
+        assert_eq!(ninth_rank, board);
+        assert!(game_state::parse_fen(Color::Black, "4Q~2k/8/8/8/8/8/8/4K3[K] w - - 0 1").is_err());
+        // a promotion mark beyond the h-file, or before any piece
+        assert!(game_state::parse_fen(Color::Black, "rnbqkbnrr~/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1").is_err());
+        assert!(game_state::parse_fen(Color::Black, "~4Q2k/8/8/8/8/8/8/4K3[] w - - 0 1").is_err());
+        // a promotion mark after an empty square, or a second one
+        assert!(game_state::parse_fen(Color::Black, "3~Q2k/8/8/8/8/8/8/4K3[] w - - 0 1").is_err());
+        assert!(game_state::parse_fen(Color::Black, "4Q~~2k/8/8/8/8/8/8/4K3[] w - - 0 1").is_err());
+    }
+}
@@ -1,643 +1,733 @@
 use std::iter::Iterator;
 use crate::chess_structs;
-use crate::chess_structs::{Board, Index2D, Color, Kind, Piece, Vector2D};
+use crate::chess_structs::{Board, Color, Kind, Piece, CastlingBitField, Move, Undo, Bitboard, Square};
 use crate::chess_structs::Kind::{Knight, Rook, Bishop, Queen};
+use crate::bitboard;
+use crate::attacks;
+use crate::move_list::MoveList;
+use crate::variant::Variant;
 
 // idea: generate most likely board first, specific for black and white
 
 #[macro_export]
 macro_rules! board_stream {
     ( $board:expr ) => {
-        $board.iter().flatten();
+        $board.iter().flatten()
     };
 }
 
-// Attack vector kind mask bits
-const KING_VECTOR:   i32 = 1 << 0;
-const QUEEN_VECTOR:  i32 = 1 << 1;
-const ROOK_VECTOR:   i32 = 1 << 2;
-const BISHOP_VECTOR: i32 = 1 << 3;
-const KNIGHT_VECTOR: i32 = 1 << 4;
-const WHITE_PAWN:    i32 = 1 << 5;
-const BLACK_PAWN:    i32 = 1 << 6;
-
-/// Attack vectors, as seen from the kings perspective
-const VECTORS: [(Vector2D, i32, i32); 8+8] = [
-    (Vector2D {x:  1, y:  1}, 7, KING_VECTOR | QUEEN_VECTOR | BISHOP_VECTOR | BLACK_PAWN),
-    (Vector2D {x:  1, y:  0}, 7, KING_VECTOR | QUEEN_VECTOR | ROOK_VECTOR),
-    (Vector2D {x:  1, y: -1}, 7, KING_VECTOR | QUEEN_VECTOR | BISHOP_VECTOR | WHITE_PAWN),
-    (Vector2D {x:  0, y: -1}, 7, KING_VECTOR | QUEEN_VECTOR | ROOK_VECTOR),
-    (Vector2D {x: -1, y: -1}, 7, KING_VECTOR | QUEEN_VECTOR | BISHOP_VECTOR | WHITE_PAWN),
-    (Vector2D {x: -1, y:  0}, 7, KING_VECTOR | QUEEN_VECTOR | ROOK_VECTOR),
-    (Vector2D {x: -1, y:  1}, 7, KING_VECTOR | QUEEN_VECTOR | BISHOP_VECTOR | BLACK_PAWN),
-    (Vector2D {x:  0, y:  1}, 7, KING_VECTOR | QUEEN_VECTOR | ROOK_VECTOR),
-    (Vector2D {x:  1, y:  2}, 1, KNIGHT_VECTOR),
-    (Vector2D {x:  2, y:  1}, 1, KNIGHT_VECTOR),
-    (Vector2D {x:  2, y: -1}, 1, KNIGHT_VECTOR),
-    (Vector2D {x:  1, y: -2}, 1, KNIGHT_VECTOR),
-    (Vector2D {x: -1, y: -2}, 1, KNIGHT_VECTOR),
-    (Vector2D {x: -2, y: -1}, 1, KNIGHT_VECTOR),
-    (Vector2D {x: -2, y:  1}, 1, KNIGHT_VECTOR),
-    (Vector2D {x: -1, y:  2}, 1, KNIGHT_VECTOR)
-];
-
 #[derive(Copy, Clone)]
 pub struct IteratorItr<'a> {
     /// The board we're generating moves from.
     board: &'a Board,
 
-    /// The x-index on the board where we are currently looking for a piece
-    x: usize,
+    /// The pieces of the side to move we have not yet generated moves for
+    pieces: Bitboard,
 
-    /// The y-index on the board where we are currently looking for a piece
-    y: usize,
+    /// Whether the drops from the pocket are still to come, after the pieces
+    drops: bool,
 }
-impl<'a> IteratorItr<'a> {
-    /// Used for when finding the next piece to move.
-    fn inc_pos(&mut self) {
-        if self.x < 7 {
-            self.x += 1;
-        }
-        else {
-            self.x = 0;
-            self.y += 1;
+
+impl Board {
+    /// The legal moves of the side to move
+    pub fn legal_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        generate_moves(self, GenType::All, &mut moves);
+        moves
+    }
+
+    /// Whether the move is legal in this position, e.g. for a move remembered from a different position
+    pub fn is_legal_move(&self, mv: &Move) -> bool {
+        let mut moves = MoveList::new();
+        if mv.is_drop() {
+            generate_drops(self, &CheckInfo::new(self), GenType::All, &mut moves);
+        } else {
+            generate_piece_moves(self, &CheckInfo::new(self), mv.from, GenType::All, &mut moves);
         }
+        moves.contains(mv)
     }
-}
 
-impl Board {
     pub fn iter(&self) -> IteratorItr {
         IteratorItr {
             board: self,
-            x: 0,
-            y: 0
+            pieces: self.colors[self.turn.index()],
+            drops: self.variant == Variant::Crazyhouse
         }
     }
-}
 
-/// Iterates over every square on the board and tries to find pieces to move.
-/// When a piece of correct color is found, returns the Iterator of that piece which
-/// will actually generate boards according to how that piece can move.
-impl<'a> Iterator for IteratorItr<'a> {
-    type Item = Box<dyn Iterator<Item = Board> + 'a>;
+    /// Plays a move on this board in place. The move is expected to come from the generator,
+    /// legality is not checked again. The returned `Undo` takes the move back through `unmake_move`.
+    pub fn make_move(&mut self, mv: &Move) -> Undo {
+        let mut undo = Undo {
+            mv: *mv,
+            captured: None,
+            en_passant: self.en_passant,
+            castling_availability: self.castling_availability,
+            checks: self.checks,
+            check_counts: self.check_counts,
+            pockets: self.pockets,
+            promoted: self.promoted,
+            exploded: bitboard::EMPTY,
+            exploded_pieces: [None; 9],
+        };
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.inc_pos();
-        if self.y > 7 {
-            None
+        // moving a king or rook away, or capturing a rook, forfeits the corresponding castling rights
+        if self.castling_availability != chess_structs::CASTLING_UNAVAILABLE {
+            self.castling_availability &= !(castling_rights_of_square(self, mv.from) | castling_rights_of_square(self, mv.to));
+        }
+
+        self.en_passant = en_passant_square(self, mv.from, mv.to);
+        if let Some(kind) = mv.promotion.filter(|_| mv.is_drop()) {
+            self.pockets[self.turn.index()][kind.index()] -= 1;
+            self.put_piece(mv.to, Piece { kind, color: self.turn });
+        } else if mv.is_castling() {
+            // both leave their squares first, in Chess960 either may land where the other one stood
+            let (king_to, rook_to) = castling_destinations(mv.from, mv.to);
+            let king = self.remove_piece(mv.from);
+            let rook = self.remove_piece(mv.to);
+            if let (Some(king), Some(rook)) = (king, rook) {
+                self.put_piece(king_to, king);
+                self.put_piece(rook_to, rook);
+            }
         } else {
-            match self.board.squares[self.y][self.x] {
-                Some(piece) => {
-                    if piece.color == self.board.turn  {
-                        match piece.kind {
-                            Kind::Knight => {
-                                let knight_itr = KnightItr::new(self.board, Index2D{x: self.x, y: self.y});
-                                Some(Box::new(knight_itr))
-                            }
-                            Kind::King => {
-                                let king_itr = KingItr::new(self.board, Index2D{x: self.x, y: self.y});
-                                Some(Box::new(king_itr))
-                            }
-                            Kind::Pawn => {
-                                let pawn_itr = PawnItr::new(self.board, Index2D{x: self.x, y: self.y});
-                                Some(Box::new(pawn_itr))
-                            }
-                            Kind::Rook => {
-                                let rook_itr = RookItr::new(self.board, Index2D{x: self.x, y: self.y});
-                                Some(Box::new(rook_itr))
-                            }
-                            Kind::Bishop => {
-                                let bishop_itr = BishopItr::new(self.board, Index2D{x: self.x, y: self.y});
-                                Some(Box::new(bishop_itr))
-                            }
-                            Kind::Queen => {
-                                let queen_itr = QueenItr::new(self.board, Index2D{x: self.x, y: self.y});
-                                Some(Box::new(queen_itr))
-                            }
-                            _ => None
-                        }
-                    } else {
-                        self.next()
-                    }
-                },
-                None => self.next()
+            let captured_at = captured_square(mv, self.turn);
+            undo.captured = self.remove_piece(captured_at);
+            if self.variant == Variant::Crazyhouse {
+                pocket_capture(self, mv, captured_at, undo.captured);
             }
+            if let Some(mut piece) = self.remove_piece(mv.from) {
+                if let Some(kind) = mv.promotion {
+                    piece.kind = kind;
+                }
+                self.put_piece(mv.to, piece);
+            }
+            if self.variant == Variant::Atomic && undo.captured.is_some() {
+                explode(self, mv.to, &mut undo);
+            }
+        }
+
+        self.turn = self.turn.invert();
+        update_checks(self);
+        if self.variant == Variant::ThreeCheck && self.checks != chess_structs::NO_CHECKS {
+            self.check_counts[self.turn.invert().index()] += 1;
         }
+        undo
     }
-}
 
-#[derive(Clone)]
-struct GenericItr<'a> {
-    initial_board: &'a Board,
-    initial_pos: Index2D,
-    current_itrn: i32
-}
+    /// Takes back the move `undo` was returned for. Moves have to be taken back in reverse order.
+    pub fn unmake_move(&mut self, undo: Undo) {
+        let mv = undo.mv;
+        self.turn = self.turn.invert();
 
+        let mut exploded = undo.exploded;
+        for piece in undo.exploded_pieces.iter() {
+            match (bitboard::pop_lsb(&mut exploded), piece) {
+                (Some(square), Some(piece)) => self.put_piece(square, *piece),
+                _ => break
+            }
+        }
 
-struct RookItr<'a>(GenericItr<'a>);
+        if mv.is_drop() {
+            self.remove_piece(mv.to);
+        } else if mv.is_castling() {
+            let (king_to, rook_to) = castling_destinations(mv.from, mv.to);
+            let king = self.remove_piece(king_to);
+            let rook = self.remove_piece(rook_to);
+            if let (Some(king), Some(rook)) = (king, rook) {
+                self.put_piece(mv.from, king);
+                self.put_piece(mv.to, rook);
+            }
+        } else {
+            if let Some(mut piece) = self.remove_piece(mv.to) {
+                if mv.promotion.is_some() {
+                    piece.kind = Kind::Pawn;
+                }
+                self.put_piece(mv.from, piece);
+            }
+            if let Some(captured) = undo.captured {
+                self.put_piece(captured_square(&mv, self.turn), captured);
+            }
+        }
 
-impl<'a> RookItr<'a> {
-    pub fn new(board: &Board, pos: Index2D) -> RookItr {
-        RookItr(GenericItr {
-            initial_board: board,
-            initial_pos: pos,
-            current_itrn: 1
-        })
+        self.en_passant = undo.en_passant;
+        self.castling_availability = undo.castling_availability;
+        self.checks = undo.checks;
+        self.check_counts = undo.check_counts;
+        self.pockets = undo.pockets;
+        self.promoted = undo.promoted;
     }
 }
 
-impl<'a> Iterator for RookItr<'a> {
-    type Item = Board;
+/// Iterates over the pieces of the side to move.
+/// For each piece, returns the Iterator of that piece which
+/// will actually generate boards according to how that piece can move.
+/// In crazyhouse, the drops come last.
+impl<'a> Iterator for IteratorItr<'a> {
+    type Item = Box<dyn Iterator<Item = (Move, Board)> + 'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        next_long_move(&mut self.0, true, false)
+        match bitboard::pop_lsb(&mut self.pieces) {
+            Some(from) => Some(Box::new(PieceMoves::new(self.board, from))),
+            None if self.drops => {
+                self.drops = false;
+                Some(Box::new(PieceMoves::drops(self.board)))
+            }
+            None => None
+        }
     }
 }
-struct BishopItr<'a>(GenericItr<'a>);
 
-impl<'a> BishopItr<'a> {
-    pub fn new(board: &Board, pos: Index2D) -> BishopItr {
-        BishopItr(GenericItr {
-            initial_board: board,
-            initial_pos: pos,
-            current_itrn: 1
-        })
-    }
+/// The legal moves of a single piece, each with the board it leads to.
+/// A thin adapter over `MoveList` for callers that want to look at the successor boards;
+/// the search works on the `MoveList` directly.
+pub struct PieceMoves<'a> {
+    board: &'a Board,
+    moves: MoveList,
+    next: usize,
 }
 
-impl<'a> Iterator for BishopItr<'a> {
-    type Item = Board;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        next_long_move(&mut self.0, false, true)
+type RookItr<'a> = PieceMoves<'a>;
+type BishopItr<'a> = PieceMoves<'a>;
+type KnightItr<'a> = PieceMoves<'a>;
+type KingItr<'a> = PieceMoves<'a>;
+type PawnItr<'a> = PieceMoves<'a>;
+
+impl<'a> PieceMoves<'a> {
+    pub fn new(board: &Board, from: Square) -> PieceMoves {
+        let mut moves = MoveList::new();
+        generate_piece_moves(board, &CheckInfo::new(board), from, GenType::All, &mut moves);
+        PieceMoves {
+            board,
+            moves,
+            next: 0
+        }
     }
-}
-struct QueenItr<'a>(GenericItr<'a>);
 
-impl<'a> QueenItr<'a> {
-    pub fn new(board: &Board, pos: Index2D) -> QueenItr {
-        QueenItr(GenericItr {
-            initial_board: board,
-            initial_pos: pos,
-            current_itrn: 1
-        })
+    /// The drops from the pocket of the side to move
+    pub fn drops(board: &Board) -> PieceMoves {
+        let mut moves = MoveList::new();
+        generate_drops(board, &CheckInfo::new(board), GenType::All, &mut moves);
+        PieceMoves {
+            board,
+            moves,
+            next: 0
+        }
     }
 }
 
-impl<'a> Iterator for QueenItr<'a> {
-    type Item = Board;
+impl<'a> Iterator for PieceMoves<'a> {
+    type Item = (Move, Board);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        next_long_move(&mut self.0, true, true)
-    }
-}
-
-fn next_long_move(itr: &mut GenericItr, is_up_down:bool, is_diagonal:bool) -> Option<Board> {
-    match itr.current_itrn {
-        1 ..= 7 => {
-            if !is_up_down {
-                itr.current_itrn = 29;
-                next_long_move(itr, is_up_down, is_diagonal)
-            } else {
-                let x = itr.current_itrn as i64;
-                let y = 0;
-                let next_board = next_move(Vector2D::new(x, y), 1, 8, itr);
-                if next_board.is_none() {
-                    itr.current_itrn = 8;
-                    next_long_move(itr, is_up_down, is_diagonal)
-                } else {
-                    next_board
-                }
-            }
-        }
-        8 ..= 14 => {
-            let x = -(itr.current_itrn as i64 -7);
-            let y = 0;
-            let next_board = next_move(Vector2D::new(x, y), 1, 15, itr);
-            if next_board.is_none() {
-                itr.current_itrn = 15;
-                next_long_move(itr, is_up_down, is_diagonal)
-            } else {
-                next_board
-            }
-        }
-        15 ..= 21 => {
-            let x = 0;
-            let y = itr.current_itrn as i64 - 14;
-            let next_board = next_move(Vector2D::new(x, y), 1, 22, itr);
-            if next_board.is_none() {
-                itr.current_itrn = 22;
-                next_long_move(itr, is_up_down, is_diagonal)
-            } else {
-                next_board
-            }
-        }
-        22 ..= 28 => {
-            let x = 0;
-            let y = -(itr.current_itrn as i64 - 21);
-            let next_board = next_move(Vector2D::new(x, y), 1, 29, itr);
-            if next_board.is_none() {
-                itr.current_itrn = 29;
-                next_long_move(itr, is_up_down, is_diagonal)
-            } else {
-                next_board
-            }
-        }//diagonal
-        29 ..= 35 => {
-            if !is_diagonal {
-              None
-            } else {
-                let x = itr.current_itrn as i64 - 28;
-                let y = itr.current_itrn as i64 - 28;
-                let next_board = next_move(Vector2D::new(x, y), 1, 36, itr);
-                if next_board.is_none() {
-                    itr.current_itrn = 36;
-                    next_long_move(itr, is_up_down, is_diagonal)
-                } else {
-                    next_board
-                }
-            }
-        }
-        36 ..= 42 => {
-            let x = -(itr.current_itrn as i64 - 35);
-            let y = itr.current_itrn as i64 - 35;
-            let next_board = next_move(Vector2D::new(x, y), 1, 43, itr);
-            if next_board.is_none() {
-                itr.current_itrn = 43;
-                next_long_move(itr, is_up_down, is_diagonal)
-            } else {
-                next_board
-            }
-        }
-        43 ..= 49 => {
-            let x = itr.current_itrn as i64 - 42;
-            let y = -(itr.current_itrn as i64 - 42);
-            let next_board = next_move(Vector2D::new(x, y), 1, 50, itr);
-            if next_board.is_none() {
-                itr.current_itrn = 50;
-                next_long_move(itr, is_up_down, is_diagonal)
-            } else {
-                next_board
-            }
-        }
-        50 ..= 56 => {
-            let x = -(itr.current_itrn as i64 - 49);
-            let y = -(itr.current_itrn as i64 - 49);
-            let next_board = next_move(Vector2D::new(x, y), 1, 57, itr);
-            if next_board.is_none() {
-                None
-            } else {
-                next_board
-            }
-        }
-        _ => None
+    fn next(&mut self) -> Option<(Move, Board)> {
+        let mv = *self.moves.get(self.next)?;
+        self.next += 1;
+        let mut new_board = *self.board;
+        new_board.make_move(&mv);
+        Some((mv, new_board))
     }
 }
 
-struct KingItr<'a>(GenericItr<'a>);
+/// Pieces a pawn may promote to, in the order the moves are generated. Only in antichess it may become a king.
+const PROMOTION_KINDS: [Kind; 5] = [Queen, Rook, Bishop, Knight, Kind::King];
+
+/// Pieces that may be dropped from the pocket, in the order the drops are generated
+const DROP_KINDS: [Kind; 5] = [Kind::Pawn, Knight, Bishop, Rook, Queen];
+
+/// Which of the legal moves to generate
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GenType {
+    /// Captures, en passant and all promotions, including the ones that capture nothing
+    Captures,
+    /// Everything else, castling included
+    Quiets,
+    /// The noisy moves: `Captures` plus the quiet moves that give check
+    Tactical,
+    All,
+}
 
-impl<'a> KingItr<'a> {
-    pub fn new(board: &Board, pos: Index2D) -> KingItr {
-        KingItr(GenericItr {
-            initial_board: board,
-            initial_pos: pos,
-            current_itrn: 1
-        })
+/// Fills `moves` with the legal moves of the side to move, piece by piece from a1 to h8.
+pub fn generate_moves(board: &Board, gen_type: GenType, moves: &mut MoveList) {
+    let check_info = CheckInfo::new(board);
+    let mut pieces = board.colors[board.turn.index()];
+    if check_info.checkers.count_ones() > 1 {
+        // only the king can get out of a double check
+        pieces = board.bitboard(board.turn, Kind::King);
     }
+    while let Some(from) = bitboard::pop_lsb(&mut pieces) {
+        generate_piece_moves(board, &check_info, from, gen_type, moves);
+    }
+    generate_drops(board, &check_info, gen_type, moves);
 }
 
-impl<'a> Iterator for KingItr<'a> {
-    type Item = Board;
+/// What the side to move has to look out for so its king is not left in check, worked out once per position
+/// so every generated move is legal without playing it.
+struct CheckInfo {
+    /// The king to keep out of check, None if there is none or it is an ordinary piece as in antichess
+    king: Option<Square>,
+    /// The enemy pieces giving check
+    checkers: Bitboard,
+    /// Own pieces that shield the king from an enemy slider, and may only move along the line to it
+    pinned: Bitboard,
+    /// Where a piece other than the king has to go: anywhere when not in check, onto the checker
+    /// or between it and a slider's target in single check, nowhere in double check
+    evasions: Bitboard,
+    /// Antichess: a capture is possible, which makes capturing compulsory
+    must_capture: bool,
+    /// The king is not in check, so it may castle if nothing else is in the way
+    may_castle: bool,
+    /// Atomic: what a capture blows up decides whether a move is legal, which pins and evasions do not cover.
+    /// The moves are played and tested instead.
+    play_to_test: bool,
+    /// The game is decided by the variant's rules, so nothing is left to play
+    game_over: bool,
+}
 
-    fn next(&mut self) -> Option<Board> {
-        let mut out_of_moves = false;
-        let board = match self.0.current_itrn {
-            1 => {
-                next_move(Vector2D::new(1, 0), 3, 2, &mut self.0)
-            }
-            2 => {
-                next_move(Vector2D::new(1, 1), 2, 3, &mut self.0)
-            }
-            3 => {
-                next_move(Vector2D::new(1, -1), 1, 4, &mut self.0)
-            }
-            4 => {
-                next_move(Vector2D::new(-1, 0), 3, 5, &mut self.0)
-            }
-            5 => {
-                next_move(Vector2D::new(-1, 1), 2, 6, &mut self.0)
-            }
-            6 => {
-                next_move(Vector2D::new(-1, -1), 1, 7, &mut self.0)
-            }
-            7 => {
-                next_move(Vector2D::new(0, -1), 3, 8, &mut self.0)
-            }
-            8 => {
-                next_move(Vector2D::new(0, 1), 2,  9, &mut self.0)
-            }
-            _ => {
-                out_of_moves = true;
-                None
-            }
+impl CheckInfo {
+    fn new(board: &Board) -> CheckInfo {
+        let must_capture = board.variant == Variant::Antichess && can_capture(board);
+        let game_over = board.is_variant_end();
+        let unrestricted = CheckInfo {
+            king: None,
+            checkers: bitboard::EMPTY,
+            pinned: bitboard::EMPTY,
+            evasions: !bitboard::EMPTY,
+            must_capture,
+            may_castle: false,
+            play_to_test: false,
+            game_over
         };
-        if out_of_moves {
-            None
-        } else {
-            match board {
-                Some(_) => board,
-                None => self.next()
+        if board.variant == Variant::Atomic {
+            return CheckInfo { may_castle: !is_atomic_check(board, board.turn), play_to_test: true, ..unrestricted };
+        }
+        let king = match board.king_square(board.turn).filter(|_| board.variant.has_checks()) {
+            Some(king) => king,
+            None => return unrestricted
+        };
+        let them = board.turn.invert();
+        let checkers = board.attackers_of(king, them);
+
+        let mut pinned = bitboard::EMPTY;
+        let diagonal = board.bitboard(them, Kind::Bishop) | board.bitboard(them, Kind::Queen);
+        let straight = board.bitboard(them, Kind::Rook) | board.bitboard(them, Kind::Queen);
+        // the sliders that would check the king if nothing stood in between
+        let mut snipers = (attacks::bishop_attacks(king, bitboard::EMPTY) & diagonal) | (attacks::rook_attacks(king, bitboard::EMPTY) & straight);
+        while let Some(sniper) = bitboard::pop_lsb(&mut snipers) {
+            let blockers = attacks::between(king, sniper) & board.occupied;
+            if blockers.count_ones() == 1 {
+                pinned |= blockers & board.colors[board.turn.index()];
             }
         }
-     }
- }
 
-struct KnightItr<'a>(GenericItr<'a>);
+        let evasions = match bitboard::lsb(checkers) {
+            None => !bitboard::EMPTY,
+            Some(checker) if checkers.count_ones() == 1 => checkers | attacks::between(king, checker),
+            Some(_) => bitboard::EMPTY
+        };
 
-impl<'a> KnightItr<'a> {
-    pub fn new(board: &Board, pos: Index2D) -> KnightItr {
-        KnightItr(GenericItr {
-            initial_board: board,
-            initial_pos: pos,
-            current_itrn: 1
-        })
+        CheckInfo { king: Some(king), checkers, pinned, evasions, must_capture, may_castle: checkers == bitboard::EMPTY, play_to_test: false, game_over }
     }
 }
 
-impl<'a> Iterator for KnightItr<'a> {
-    type Item = Board;
+/// Whether the side to move has any capture, en passant included
+pub fn can_capture(board: &Board) -> bool {
+    let us = board.turn;
+    let en_passant = board.en_passant
+        .map_or(bitboard::EMPTY, |square| attacks::pawn_attacks(square, us.invert()) & board.bitboard(us, Kind::Pawn));
+    board.attack_map(us) & board.colors[us.invert().index()] != bitboard::EMPTY || en_passant != bitboard::EMPTY
+}
 
-    fn next(&mut self) -> Option<Board> {
-        let mut out_of_moves = false;
-        let board = match self.0.current_itrn {
-            1 => {
-                next_move(Vector2D::new(2, 1), 1, 2,  &mut self.0)
-            }
-            2 => {
-                next_move(Vector2D::new(2, -1), 1, 3,  &mut self.0)
-            }
-            3 => {
-                next_move(Vector2D::new(-2, 1), 1, 4, &mut self.0)
-            }
-            4 => {
-                next_move(Vector2D::new(-2, 1), 1, 5, &mut self.0)
-            }
-            5 => {
-                next_move(Vector2D::new(1, 2), 1, 6, &mut self.0)
-            }
-            6 => {
-                next_move(Vector2D::new(1, -2), 1, 7, &mut self.0)
-            }
-            7 => {
-                next_move(Vector2D::new(-1, 2), 1, 8,  &mut self.0)
-            }
-            8 => {
-                next_move(Vector2D::new(-1, -2), 1, 9, &mut self.0)
-            }
-            _ => {
-                out_of_moves = true;
-                None
+/// The ordinary moves of the piece on `from`, each promotion kind for moves onto the last rank,
+/// and finally castling or en passant.
+fn generate_piece_moves(board: &Board, check_info: &CheckInfo, from: Square, gen_type: GenType, moves: &mut MoveList) {
+    let piece = match board.piece_at(from) {
+        Some(piece) if piece.color == board.turn => piece,
+        _ => return
+    };
+    if check_info.game_over {
+        return;
+    }
+    let enemies = board.colors[board.turn.invert().index()];
+    let mut targets = match piece.kind {
+        Kind::Pawn => pawn_targets(board, from),
+        Kind::Knight => attacks::knight_attacks(from),
+        Kind::Bishop => attacks::bishop_attacks(from, board.occupied),
+        Kind::Rook => attacks::rook_attacks(from, board.occupied),
+        Kind::Queen => attacks::queen_attacks(from, board.occupied),
+        Kind::King => attacks::king_attacks(from),
+    } & !board.colors[board.turn.index()];
+
+    if piece.kind != Kind::King {
+        targets &= check_info.evasions;
+        if let Some(king) = check_info.king.filter(|_| check_info.pinned & bitboard::bit(from) != 0) {
+            targets &= attacks::line(king, from);
+        }
+    }
+    if check_info.must_capture {
+        targets &= enemies;
+    }
+    // an atomic king would blow itself up
+    if piece.kind == Kind::King && board.variant == Variant::Atomic {
+        targets &= !enemies;
+    }
+
+    let promotions = if piece.kind == Kind::Pawn { targets & (bitboard::RANK_1 | bitboard::RANK_8) } else { bitboard::EMPTY };
+    let noisy = enemies | promotions;
+    let mut targets = match gen_type {
+        GenType::Captures => targets & noisy,
+        GenType::Quiets => targets & !noisy,
+        GenType::Tactical | GenType::All => targets,
+    };
+
+    while let Some(to) = bitboard::pop_lsb(&mut targets) {
+        // the king may not step onto an attacked square, including the ones behind it on a checking slider's ray
+        if piece.kind == Kind::King && check_info.king.is_some() && attacks::attackers_with(board, to, board.turn.invert(), board.occupied & !bitboard::bit(from)) != bitboard::EMPTY {
+            continue;
+        }
+        let flags = if enemies & bitboard::bit(to) != 0 { chess_structs::CAPTURE } else { chess_structs::QUIET_MOVE };
+        let mv = Move::new(from, to, None, flags);
+        if gen_type == GenType::Tactical && noisy & bitboard::bit(to) == 0 && !gives_check(board, &mv) {
+            continue;
+        }
+        if promotions & bitboard::bit(to) != 0 {
+            let kinds = if board.variant == Variant::Antichess { &PROMOTION_KINDS[..] } else { &PROMOTION_KINDS[..4] };
+            for &kind in kinds.iter() {
+                push_legal(board, check_info, Move::new(from, to, Some(kind), flags), moves);
             }
-        };
-        if out_of_moves {
-            None
         } else {
-            match board {
-                Some(_) => board,
-                None => self.next()
-            }
+            push_legal(board, check_info, mv, moves);
         }
     }
-}
-
-struct PawnItr<'a>(GenericItr<'a>);
 
-impl<'a> PawnItr<'a> {
-    pub fn new(board: &Board, pos: Index2D) -> PawnItr {
-        PawnItr(GenericItr {
-            initial_board: board,
-            initial_pos: pos,
-            current_itrn: 1
-        })
+    let special = match piece.kind {
+        Kind::King if gen_type != GenType::Captures && check_info.may_castle => {
+            [castling_move(board, from, true), castling_move(board, from, false)]
+        }
+        Kind::Pawn if gen_type != GenType::Quiets => [en_passant_move(board, check_info.king, from), None],
+        _ => [None, None]
+    };
+    for mv in special.iter().flatten() {
+        // castling is the only quiet one of these
+        if gen_type != GenType::Tactical || mv.is_capture() || gives_check(board, mv) {
+            push_legal(board, check_info, *mv, moves);
+        }
     }
 }
 
-impl<'a> Iterator for PawnItr<'a> {
-    type Item = Board;
+/// Adds a generated move, unless it breaks a rule pins and evasions do not cover: in atomic, it has to be played
+/// to test whether it leaves the king in check or blown up, and in racing kings, it must not give check.
+fn push_legal(board: &Board, check_info: &CheckInfo, mv: Move, moves: &mut MoveList) {
+    if check_info.play_to_test && !is_atomic_legal(board, &mv) {
+        return;
+    }
+    if board.variant == Variant::RacingKings && gives_check(board, &mv) {
+        return;
+    }
+    moves.push(mv);
+}
 
-    fn next(&mut self) -> Option<Board> {
-        let mut side = 1;
-        let mut start_pos_y = 1;
-        if self.0.initial_board.turn == Color::Black {
-            side = -1;
-            start_pos_y = 6;
+/// Racing kings: whether the king of the side to move has a legal step onto the eighth rank.
+/// Tested without generating the moves, which ask whether the race is over already.
+pub fn can_reach_goal(board: &Board) -> bool {
+    let king = match board.king_square(board.turn) {
+        Some(king) => king,
+        None => return false
+    };
+    let them = board.turn.invert();
+    let occupied = board.occupied & !bitboard::bit(king);
+    let mut steps = attacks::king_attacks(king) & bitboard::RANK_8 & !board.colors[board.turn.index()];
+    while let Some(to) = bitboard::pop_lsb(&mut steps) {
+        let flags = if board.occupied & bitboard::bit(to) != 0 { chess_structs::CAPTURE } else { chess_structs::QUIET_MOVE };
+        if attacks::attackers_with(board, to, them, occupied) & !bitboard::bit(to) == bitboard::EMPTY
+            && !gives_check(board, &Move::new(king, to, None, flags)) {
+            return true;
         }
+    }
+    false
+}
 
-        let mut out_of_moves = false;
-        let board = match self.0.current_itrn {
-            1 => {
-                if self.0.initial_pos.y != start_pos_y {
-                    self.0.current_itrn += 1;
-                   None
-                } else {
-                    next_move2(Vector2D::new(0, 2 * side), 1, 2,  &mut self.0, is_square_empty)
-                }
-            }
-            2 => {
-                next_move2(Vector2D::new(0, 1 * side), 1, 3,   &mut self.0, is_square_empty)
-            }
-            3 => {
-                next_move2(Vector2D::new(-1, 1 * side), 1, 4, &mut self.0, is_square_enemy)
-            }
-            4 => {
-                next_move2(Vector2D::new(1, 1 * side), 1, 5, &mut self.0, is_square_enemy)
-            }
-            _ => {
-                out_of_moves = true;
-                None
-            }
-        };
-        if out_of_moves {
-            None
-        } else {
-            match board {
-                Some(_) => board,
-                None => self.next()
+/// Crazyhouse: the pieces in the pocket of the side to move put on the empty squares, pawns not on the first or last rank.
+/// In check, only the drops that block it.
+fn generate_drops(board: &Board, check_info: &CheckInfo, gen_type: GenType, moves: &mut MoveList) {
+    if board.variant != Variant::Crazyhouse || gen_type == GenType::Captures {
+        return;
+    }
+    let empty = !board.occupied & check_info.evasions;
+    for &kind in DROP_KINDS.iter() {
+        if board.pockets[board.turn.index()][kind.index()] == 0 {
+            continue;
+        }
+        let mut targets = if kind == Kind::Pawn { empty & !(bitboard::RANK_1 | bitboard::RANK_8) } else { empty };
+        while let Some(to) = bitboard::pop_lsb(&mut targets) {
+            let mv = Move::drop(kind, to);
+            if gen_type != GenType::Tactical || gives_check(board, &mv) {
+                moves.push(mv);
             }
         }
     }
 }
 
-pub fn create_new_board(board: &Board, from: Index2D, to: Index2D) -> Board {
-    //println!("from x: {}", from.x);
-    //println!("from y: {}", from.y);
-    //println!("to x: {}", to.x);
-    //println!("to y: {}", to.y);
+/// Pushes and captures of the pawn on `from`, without en passant.
+/// In horde, pawns on the first rank may push two squares as well.
+fn pawn_targets(board: &Board, from: Square) -> Bitboard {
+    let pawn = bitboard::bit(from);
+    let empty = !board.occupied;
+    let horde = board.variant == Variant::Horde;
+    let (single_push, double_push) = match board.turn {
+        Color::White => {
+            let single_push = bitboard::north(pawn) & empty;
+            let double_from = bitboard::RANK_3 | if horde { bitboard::RANK_2 } else { bitboard::EMPTY };
+            (single_push, bitboard::north(single_push & double_from) & empty)
+        }
+        Color::Black => {
+            let single_push = bitboard::south(pawn) & empty;
+            let double_from = bitboard::RANK_6 | if horde { bitboard::RANK_7 } else { bitboard::EMPTY };
+            (single_push, bitboard::south(single_push & double_from) & empty)
+        }
+    };
+    let captures = attacks::pawn_attacks(from, board.turn) & board.colors[board.turn.invert().index()];
+    single_push | double_push | captures
+}
+
+/// Whether the move checks the opponent's king, directly or by uncovering a slider.
+fn gives_check(board: &Board, mv: &Move) -> bool {
+    let king = match board.king_square(board.turn.invert()).filter(|_| board.variant.has_checks()) {
+        Some(king) => king,
+        None => return false
+    };
+    let mut pieces = board.pieces[board.turn.index()];
+    let mut occupied = board.occupied;
+    if mv.is_castling() {
+        let (king_to, rook_to) = castling_destinations(mv.from, mv.to);
+        pieces[Kind::King.index()] = (pieces[Kind::King.index()] & !bitboard::bit(mv.from)) | bitboard::bit(king_to);
+        pieces[Kind::Rook.index()] = (pieces[Kind::Rook.index()] & !bitboard::bit(mv.to)) | bitboard::bit(rook_to);
+        occupied = (occupied & !bitboard::bit(mv.from) & !bitboard::bit(mv.to)) | bitboard::bit(king_to) | bitboard::bit(rook_to);
+    } else if let Some(kind) = mv.promotion.filter(|_| mv.is_drop()) {
+        pieces[kind.index()] |= bitboard::bit(mv.to);
+        occupied |= bitboard::bit(mv.to);
+    } else if let Some(kind) = Kind::ALL.iter().find(|kind| pieces[kind.index()] & bitboard::bit(mv.from) != 0) {
+        pieces[kind.index()] &= !bitboard::bit(mv.from);
+        pieces[mv.promotion.unwrap_or(*kind).index()] |= bitboard::bit(mv.to);
+        occupied = (occupied & !bitboard::bit(mv.from)) | bitboard::bit(mv.to);
+    }
+    if mv.is_en_passant() {
+        occupied &= !bitboard::bit(captured_square(mv, board.turn));
+    }
+    attacks::attackers_of_set(&pieces, king, board.turn, occupied) != bitboard::EMPTY
+}
+
+/// The board after an ordinary move or capture from `from` to `to`
+pub fn create_new_board(board: &Board, from: Square, to: Square) -> Board {
+    let flags = if board.occupied & bitboard::bit(to) != 0 { chess_structs::CAPTURE } else { chess_structs::QUIET_MOVE };
     let mut board = *board;
-    board.squares[to.y][to.x] = board.squares[from.y][from.x];
-    board.squares[from.y][from.x] = None;
-    board.turn = board.turn.invert();
+    board.make_move(&Move::new(from, to, None, flags));
+    board
+}
 
-    let (is_white_checked, is_black_checked) = checks(&board);
+/// Crazyhouse: the captured piece goes into the capturer's pocket, as a pawn if it was promoted.
+/// Promoted pieces stay marked as such where they move.
+fn pocket_capture(board: &mut Board, mv: &Move, captured_at: Square, captured: Option<Piece>) {
+    if let Some(piece) = captured {
+        let kind = if board.promoted & bitboard::bit(captured_at) != 0 { Kind::Pawn } else { piece.kind };
+        board.pockets[board.turn.index()][kind.index()] += 1;
+    }
+    let was_promoted = board.promoted & bitboard::bit(mv.from) != 0;
+    board.promoted &= !(bitboard::bit(mv.from) | bitboard::bit(captured_at));
+    if was_promoted || mv.promotion.is_some() {
+        board.promoted |= bitboard::bit(mv.to);
+    }
+}
 
-    board.checks = chess_structs::NO_CHECKS;
-    if is_white_checked {
-        board.checks |= chess_structs::WHITE_IS_CHECKED;
+/// Atomic: a capture blows up the capturing piece on `center` and every piece but the pawns around it,
+/// which `undo` keeps to put them back. Blown up kings and rooks take their castling rights with them.
+fn explode(board: &mut Board, center: Square, undo: &mut Undo) {
+    let pawns = board.bitboard(Color::White, Kind::Pawn) | board.bitboard(Color::Black, Kind::Pawn);
+    let mut blast = (attacks::king_attacks(center) & board.occupied & !pawns) | bitboard::bit(center);
+    undo.exploded = blast;
+    let mut index = 0;
+    while let Some(square) = bitboard::pop_lsb(&mut blast) {
+        board.castling_availability &= !castling_rights_of_square(board, square);
+        undo.exploded_pieces[index] = board.remove_piece(square);
+        index += 1;
     }
-    if is_black_checked {
-        board.checks |= chess_structs::BLACK_IS_CHECKED;
+}
+
+/// Atomic: whether the king of `color` is attacked. Kings cannot capture, and kings next to each other
+/// cannot be taken at all, since the explosion would take the capturing side's king along.
+fn is_atomic_check(board: &Board, color: Color) -> bool {
+    match (board.king_square(color), board.king_square(color.invert())) {
+        (Some(king), Some(their_king)) => attacks::king_attacks(king) & bitboard::bit(their_king) == bitboard::EMPTY
+            && board.attackers_of(king, color.invert()) & !bitboard::bit(their_king) != bitboard::EMPTY,
+        _ => false
     }
+}
 
-    board
+/// Atomic: a move is legal if it does not blow up the own king, and either blows up the enemy king
+/// or does not leave the own one in check.
+fn is_atomic_legal(board: &Board, mv: &Move) -> bool {
+    let mut after = *board;
+    after.make_move(mv);
+    let (us, them) = (board.turn, board.turn.invert());
+    after.king_square(us).is_some() && (after.king_square(them).is_none() || !is_atomic_check(&after, us))
 }
 
-fn next_move (vect: Vector2D, inc: i32, inc2:i32, itr: &mut GenericItr) -> Option<Board> {
-    next_move2(vect, inc, inc2, itr, is_square_empty_or_enemy)
+/// The square of the piece a move takes, which is not the destination for en passant.
+fn captured_square(mv: &Move, mover: Color) -> Square {
+    match (mv.is_en_passant(), mover) {
+        (false, _) => mv.to,
+        (true, Color::White) => mv.to - 8,
+        (true, Color::Black) => mv.to + 8,
+    }
 }
 
-fn next_move2 (vect: Vector2D, inc: i32, inc2:i32, itr: &mut GenericItr, square_checker: fn(board: &Board, to: Index2D) -> bool) -> Option<Board> {
-    let new_pos = itr.initial_pos + vect;
+/// Where king and rook go when the king on `king_from` castles with the rook on `rook_from`:
+/// the g- and f-file kingside, the c- and d-file queenside, wherever they started from.
+fn castling_destinations(king_from: Square, rook_from: Square) -> (Square, Square) {
+    let rank = king_from - king_from % 8;
+    if rook_from > king_from {
+        (rank + 6, rank + 5)
+    } else {
+        (rank + 2, rank + 3)
+    }
+}
 
-    if let Some(new_pos) = new_pos {
-        if new_pos.is_out_of_board() {
-            itr.current_itrn += inc;
-            None
-        }
-        else if square_checker(itr.initial_board, new_pos) {
-            if is_square_enemy(itr.initial_board, new_pos) {
-                itr.current_itrn = inc2;
-            } else {
-                itr.current_itrn +=1;
-            }
-            let new_board = create_new_board(itr.initial_board, itr.initial_pos, new_pos);
-            match itr.initial_board.turn {
-                Color::White => if new_board.checks & chess_structs::WHITE_IS_CHECKED > 0 { None } else { Some(new_board) },
-                Color::Black => if new_board.checks & chess_structs::BLACK_IS_CHECKED > 0 { None } else { Some(new_board) }
-            }
-        }
-        else {
-            itr.current_itrn += 1;
-            None
-        }
+/// The square skipped by a pawn double push, which the opponent may capture en passant on the next move.
+/// A horde pawn pushing two squares from the first rank cannot be taken en passant.
+fn en_passant_square(board: &Board, from: Square, to: Square) -> Option<Square> {
+    let is_pawn = board.pieces[board.turn.index()][Kind::Pawn.index()] & bitboard::bit(from) != 0;
+    let from_second_rank = bitboard::bit(from) & (bitboard::RANK_2 | bitboard::RANK_7) != bitboard::EMPTY;
+    if is_pawn && from_second_rank && (from as i64 - to as i64).abs() == 16 {
+        Some((from + to) / 2)
     } else {
-        itr.current_itrn += 1;
         None
     }
 }
 
-pub fn is_square_empty_or_enemy(board: &Board, to: Index2D) -> bool {
-    match board.squares[to.y][to.x] {
-        Some(piece) =>  piece.color != board.turn,
-        None => true
+/// The castling rights that depend on the given square still holding its original king or rook.
+fn castling_rights_of_square(board: &Board, square: Square) -> CastlingBitField {
+    let mut rights = chess_structs::CASTLING_UNAVAILABLE;
+    for (index, &rook) in board.castling_rooks.iter().enumerate() {
+        if rook == square {
+            rights |= 1 << index;
+        }
     }
+    if board.bitboard(Color::White, Kind::King) & bitboard::bit(square) != 0 {
+        rights |= chess_structs::WHITE_KINGSIDE | chess_structs::WHITE_QUEENSIDE;
+    }
+    if board.bitboard(Color::Black, Kind::King) & bitboard::bit(square) != 0 {
+        rights |= chess_structs::BLACK_KINGSIDE | chess_structs::BLACK_QUEENSIDE;
+    }
+    rights
+}
+
+/// Sets `Board::checks` from the pieces on the board.
+/// Only the side to move is looked at, the side that just moved cannot be left in check by a legal move.
+pub fn update_checks(board: &mut Board) {
+    let is_checked = match board.king_square(board.turn).filter(|_| board.variant.has_checks()) {
+        Some(_) if board.variant == Variant::Atomic => is_atomic_check(board, board.turn),
+        Some(king) => is_square_attacked(board, king, board.turn),
+        None => false
+    };
+
+    board.checks = match (is_checked, board.turn) {
+        (false, _) => chess_structs::NO_CHECKS,
+        (true, Color::White) => chess_structs::WHITE_IS_CHECKED,
+        (true, Color::Black) => chess_structs::BLACK_IS_CHECKED,
+    };
 }
 
-pub fn is_square_empty(board: &Board, to: Index2D) -> bool {
-    match board.squares[to.y][to.x] {
-        Some(_) =>  false,
-        None => true
+/// Captures the pawn that just made a double push, if it skipped a square this pawn attacks.
+/// Both pawns leave their squares at once, which pins and evasions do not cover: taking them both off a rank
+/// may expose the king, and capturing the checking pawn evades a check without landing on its square.
+/// So the position after the capture is tested for checks directly, unless there is no `king` to keep safe.
+fn en_passant_move(board: &Board, king: Option<Square>, from: Square) -> Option<Move> {
+    let to = board.en_passant?;
+    if attacks::pawn_attacks(from, board.turn) & bitboard::bit(to) == 0 {
+        return None;
     }
+
+    let mv = Move::new(from, to, None, chess_structs::CAPTURE | chess_structs::EN_PASSANT);
+    if let Some(king) = king {
+        let captured = bitboard::bit(captured_square(&mv, board.turn));
+        let occupied = (board.occupied & !bitboard::bit(from) & !captured) | bitboard::bit(to);
+        if attacks::attackers_with(board, king, board.turn.invert(), occupied) & !captured != bitboard::EMPTY {
+            return None;
+        }
+    }
+    Some(mv)
 }
 
-pub fn is_square_enemy(board: &Board, to: Index2D) -> bool {
-    match board.squares[to.y][to.x] {
-        Some(piece) =>  piece.color != board.turn,
-        None => false
+/// Tries to castle kingside or queenside with the king on `king_from` and the rook the castling right belongs to.
+/// The squares king and rook pass and land on must be empty apart from the two of them, so in Chess960
+/// they may pass or land on each other's squares. The king may not castle out of, through, or into check.
+fn castling_move(board: &Board, king_from: Square, is_kingside: bool) -> Option<Move> {
+    let right = match (board.turn, is_kingside) {
+        (Color::White, true) => chess_structs::WHITE_KINGSIDE,
+        (Color::White, false) => chess_structs::WHITE_QUEENSIDE,
+        (Color::Black, true) => chess_structs::BLACK_KINGSIDE,
+        (Color::Black, false) => chess_structs::BLACK_QUEENSIDE,
+    };
+    if board.castling_availability & right == 0 {
+        return None;
+    }
+
+    // a board set up by hand may have castling rights its pieces do not fit
+    let rook_from = board.castling_rook(right);
+    if board.bitboard(board.turn, Kind::Rook) & bitboard::bit(rook_from) == 0 || rook_from / 8 != king_from / 8 || (rook_from > king_from) != is_kingside {
+        return None;
+    }
+
+    let (king_to, rook_to) = castling_destinations(king_from, rook_from);
+    let king_path = attacks::between(king_from, king_to) | bitboard::bit(king_to);
+    let rook_path = attacks::between(rook_from, rook_to) | bitboard::bit(rook_to);
+    let others = board.occupied & !bitboard::bit(king_from) & !bitboard::bit(rook_from);
+    if (king_path | rook_path) & others != bitboard::EMPTY {
+        return None;
     }
+    let them = board.turn.invert();
+    if board.attack_map(them) & king_path != bitboard::EMPTY {
+        return None;
+    }
+    // in Chess960 the rook may have stood between the king's destination and an enemy slider
+    let occupied = others | bitboard::bit(king_to) | bitboard::bit(rook_to);
+    if attacks::attackers_with(board, king_to, them, occupied) != bitboard::EMPTY {
+        return None;
+    }
+
+    Some(Move::new(king_from, rook_from, None, chess_structs::CASTLING))
 }
 
 /// Return which kings are in check. Order: white, black
 fn checks(board: &Board) -> (bool, bool) {
+    let is_checked = |color: Color| match board.king_square(color) {
+        Some(king) => is_square_attacked(board, king, color),
+        None => false
+    };
 
-    let mut is_white_checked = false;
-    let mut is_black_checked = false;
-
-    board.squares
-        .iter()
-        .enumerate()
-        .flat_map(|(y, squares)| squares
-            .iter()
-            .enumerate()
-            .filter_map(move |(x, square)| match square {
-                Some(piece)=> Some((Index2D::new(x, y), piece)),
-                None => None
-            })
-        )
-        .filter(|(_, piece)| piece.kind == Kind::King)
-        .map(|(pos, king)| is_check(board, pos, king))
-        .for_each(|(color, is_checked)| match color {
-            Color::White => is_white_checked = is_checked,
-            Color::Black => is_black_checked = is_checked
-        });
-
-    (is_white_checked, is_black_checked)
-}
-
-fn is_check(board: &Board, pos: Index2D, king: &Piece) -> (Color, bool) {
-
-    let enemy_color = king.color.invert();
-    let mut is_check = false;
-
-    'outer: for (vec, reps, kind_mask) in VECTORS.iter() {
-        let mut next_square = pos;
-        for rep in 0..*reps {
-            next_square += vec;
-            if next_square.is_out_of_board() {
-                break;
-            }
-            if let Some(piece) = board.squares[next_square.y][next_square.x] {
-                if piece.color == enemy_color {
-                    // check if this piece can attack along this vector
-                    is_check = match piece.kind {
-                        Kind::Pawn => match piece.color {
-                            Color::Black => rep == 0 && kind_mask & BLACK_PAWN > 0,
-                            Color::White => rep == 0 && kind_mask & WHITE_PAWN > 0
-                        },
-                        Kind::Bishop => kind_mask & BISHOP_VECTOR > 0,
-                        Kind::Knight => kind_mask & KNIGHT_VECTOR > 0,
-                        Kind::Rook => kind_mask & ROOK_VECTOR > 0,
-                        Kind::King => rep == 0 && kind_mask & KING_VECTOR > 0,
-                        Kind::Queen => kind_mask & QUEEN_VECTOR > 0,
-                    };
-                    if is_check {
-                        break 'outer; // no need to search any more
-                    }
-                } else {
-                    break; // a friendly piece is blocking this attack vector; on to the next vector!
-                }
-            }
-        }
-    }
-
-    (king.color, is_check)
+    (is_checked(Color::White), is_checked(Color::Black))
 }
 
+/// Whether a piece of color `defender` standing on `square` would be attacked by the opponent.
+fn is_square_attacked(board: &Board, square: Square, defender: Color) -> bool {
+    board.attackers_of(square, defender.invert()) != bitboard::EMPTY
+}
 
 mod tests {
+    use crate::bitboard;
     use crate::chess_structs;
-    use crate::chess_structs::{Board, Piece, Index2D, Color, Kind};
-    use crate::generator::{KingItr, KnightItr, RookItr, BishopItr};
+    use crate::chess_structs::{Board, Piece, Index2D, Color, Kind, Move, square};
+    use crate::generator::{KingItr, KnightItr, RookItr, BishopItr, PawnItr};
     use crate::generator;
+    use crate::generator::GenType;
+    use crate::game_state;
+    use crate::move_list::MoveList;
+    use crate::variant::Variant;
 
     #[test]
     fn king_test() {
-        let board: Board = Board{
-            squares: [
-                [None; 8], // bottom of board (y = rank -1 = 0)
-                [None; 8],
-                [None, None, None, None, Some(Piece{kind: Kind::King, color: Color::White}), None, None, None],
-                [None, None, None, None, Some(Piece{kind: Kind::Pawn, color: Color::Black}), None, None, None],
-                [None; 8],
-                [None; 8],
-                [None; 8],
-                [None; 8], // top of board (y = rank -1 = 7)
-        ],
-            turn: Color::White,
-            en_passant: None,
-            castling_availability: chess_structs::CASTLING_FULL,
-            checks: chess_structs::NO_CHECKS
-        };
-        let pos = Index2D {x: 4, y:2};
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_FULL, chess_structs::NO_CHECKS);
+        board.put_piece(square(4, 2), Piece{kind: Kind::King, color: Color::White});
+        board.put_piece(square(4, 3), Piece{kind: Kind::Pawn, color: Color::Black});
+        let pos = square(4, 2);
         let mut king_itr= KingItr::new(&board, pos);
         assert!(king_itr.next().is_some());
         assert!(king_itr.next().is_some());
@@ -648,7 +738,7 @@ mod tests {
         // two moves are blocked because it puts the king in check, thus expect 6 positions
         assert!(king_itr.next().is_none());
 
-        let mut new_board:Option<Board> = None;
+        let mut new_board:Option<(Move, Board)> = None;
 
         let mut move_itr = board.iter();
         if let Some(mut i) = move_itr.next() {
@@ -660,23 +750,10 @@ mod tests {
     }
     #[test]
     fn knight_test() {
-        let board: Board = Board{
-            squares: [
-                [None; 8], // bottom of board (y = rank -1 = 0)
-                [None; 8],
-                [None, None, None, None, Some(Piece{kind: Kind::Knight, color: Color::White}), None, None, None],
-                [None, None, None, None, Some(Piece{kind: Kind::Pawn, color: Color::Black}), None, None, None],
-                [None; 8],
-                [None; 8],
-                [None; 8],
-                [None; 8], // top of board (y = rank -1 = 7)
-            ],
-            turn: Color::White,
-            en_passant: None,
-            castling_availability: chess_structs::CASTLING_FULL,
-            checks: chess_structs::NO_CHECKS
-        };
-        let pos = Index2D {x: 4, y:2};
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_FULL, chess_structs::NO_CHECKS);
+        board.put_piece(square(4, 2), Piece{kind: Kind::Knight, color: Color::White});
+        board.put_piece(square(4, 3), Piece{kind: Kind::Pawn, color: Color::Black});
+        let pos = square(4, 2);
         let mut knight_iter = KnightItr::new(&board, pos);
         assert!(knight_iter.next().is_some());
         assert!(knight_iter.next().is_some());
@@ -690,7 +767,7 @@ mod tests {
         assert!(knight_iter.next().is_none());
 
 
-        let mut new_board:Option<Board> = None;
+        let mut new_board:Option<(Move, Board)> = None;
 
         let mut move_itr = board.iter();
         if let Some(mut i) = move_itr.next() {
@@ -719,23 +796,10 @@ mod tests {
 
     #[test]
     fn rook_test() {
-        let board: Board = Board{
-            squares: [
-                [None; 8], // bottom of board (y = rank -1 = 0)
-                [None; 8],
-                [None, None, None, None, Some(Piece{kind: Kind::Rook, color: Color::Black}), None, None, None],
-                [None, None, None, None, Some(Piece{kind: Kind::Pawn, color: Color::Black}), None, None, None],
-                [None; 8],
-                [None; 8],
-                [None; 8],
-                [None; 8], // top of board (y = rank -1 = 7)
-            ],
-            turn: Color::Black,
-            en_passant: None,
-            castling_availability: chess_structs::CASTLING_FULL,
-            checks: chess_structs::NO_CHECKS
-        };
-        let pos = Index2D {x: 4, y:2};
+        let mut board = Board::new(Color::Black, None, chess_structs::CASTLING_FULL, chess_structs::NO_CHECKS);
+        board.put_piece(square(4, 2), Piece{kind: Kind::Rook, color: Color::Black});
+        board.put_piece(square(4, 3), Piece{kind: Kind::Pawn, color: Color::Black});
+        let pos = square(4, 2);
         let mut rook_iter = RookItr::new(&board, pos);
         assert!(rook_iter.next().is_some());
         assert!(rook_iter.next().is_some());
@@ -751,23 +815,10 @@ mod tests {
     }
     #[test]
     fn bishop_test() {
-        let board: Board = Board{
-            squares: [
-                [None; 8], // bottom of board (y = rank -1 = 0)
-                [None, None, Some(Piece{kind: Kind::Bishop, color: Color::Black}), None, None, None,  None, None],
-                [None; 8],
-                [None, None, None, None, Some(Piece{kind: Kind::Pawn, color: Color::Black}), None, None, None],
-                [None; 8],
-                [None; 8],
-                [None; 8],
-                [None; 8], // top of board (y = rank -1 = 7)
-            ],
-            turn: Color::Black,
-            en_passant: None,
-            castling_availability: chess_structs::CASTLING_FULL,
-            checks: chess_structs::NO_CHECKS
-        };
-        let pos = Index2D {x: 2, y:1};
+        let mut board = Board::new(Color::Black, None, chess_structs::CASTLING_FULL, chess_structs::NO_CHECKS);
+        board.put_piece(square(2, 1), Piece{kind: Kind::Bishop, color: Color::Black});
+        board.put_piece(square(4, 3), Piece{kind: Kind::Pawn, color: Color::Black});
+        let pos = square(2, 1);
         let mut bishop_iter = BishopItr::new(&board, pos);
         assert!(bishop_iter.next().is_some());
         assert!(bishop_iter.next().is_some());
@@ -778,6 +829,348 @@ mod tests {
         assert!(bishop_iter.next().is_none());
     }
 
+    fn castling_test_board() -> Board {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_FULL, chess_structs::NO_CHECKS);
+        board.put_piece(square(0, 0), Piece { kind: Kind::Rook, color: Color::White });
+        board.put_piece(square(4, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(7, 0), Piece { kind: Kind::Rook, color: Color::White });
+        board.put_piece(square(0, 7), Piece { kind: Kind::Rook, color: Color::Black });
+        board.put_piece(square(4, 7), Piece { kind: Kind::King, color: Color::Black });
+        board.put_piece(square(7, 7), Piece { kind: Kind::Rook, color: Color::Black });
+        board
+    }
+
+    fn is_king(board: &Board, x: usize, y: usize) -> bool {
+        board.piece_at(square(x, y)).map(|piece| piece.kind) == Some(Kind::King)
+    }
+
+    #[test]
+    fn castling_test() {
+        let board = castling_test_board();
+        let boards: Vec<Board> = KingItr::new(&board, square(4, 0)).map(|(_, b)| b).collect();
+        // d1, d2, e2, f2, f1 + both castlings
+        assert_eq!(boards.len(), 7);
+
+        let kingside = boards.iter().find(|b| is_king(b, 6, 0)).unwrap();
+        assert_eq!(kingside.piece_at(square(5, 0)).map(|piece| piece.kind), Some(Kind::Rook));
+        assert!(kingside.piece_at(square(7, 0)).is_none());
+        assert_eq!(kingside.castling_availability, chess_structs::BLACK_KINGSIDE | chess_structs::BLACK_QUEENSIDE);
+
+        let queenside = boards.iter().find(|b| is_king(b, 2, 0)).unwrap();
+        assert_eq!(queenside.piece_at(square(3, 0)).map(|piece| piece.kind), Some(Kind::Rook));
+        assert!(queenside.piece_at(square(0, 0)).is_none());
+    }
+
+    #[test]
+    fn castling_blocked_test() {
+        // no castling through an attacked square
+        let mut board = castling_test_board();
+        board.put_piece(square(5, 7), Piece { kind: Kind::Rook, color: Color::Black });
+        let boards: Vec<Board> = KingItr::new(&board, square(4, 0)).map(|(_, b)| b).collect();
+        assert!(!boards.iter().any(|b| is_king(b, 6, 0)));
+        assert!(boards.iter().any(|b| is_king(b, 2, 0)));
+
+        // no castling out of check
+        let mut board = castling_test_board();
+        board.remove_piece(square(4, 7));
+        board.put_piece(square(3, 7), Piece { kind: Kind::King, color: Color::Black });
+        board.put_piece(square(4, 5), Piece { kind: Kind::Rook, color: Color::Black });
+        let boards: Vec<Board> = KingItr::new(&board, square(4, 0)).map(|(_, b)| b).collect();
+        assert!(!boards.iter().any(|b| is_king(b, 6, 0) || is_king(b, 2, 0)));
+
+        // no castling with a piece in the way, even if the king does not pass that square
+        let mut board = castling_test_board();
+        board.put_piece(square(1, 0), Piece { kind: Kind::Knight, color: Color::White });
+        let boards: Vec<Board> = KingItr::new(&board, square(4, 0)).map(|(_, b)| b).collect();
+        assert!(!boards.iter().any(|b| is_king(b, 2, 0)));
+
+        // no castling without the right
+        let mut board = castling_test_board();
+        board.castling_availability = chess_structs::WHITE_QUEENSIDE;
+        let boards: Vec<Board> = KingItr::new(&board, square(4, 0)).map(|(_, b)| b).collect();
+        assert!(!boards.iter().any(|b| is_king(b, 6, 0)));
+        assert!(boards.iter().any(|b| is_king(b, 2, 0)));
+    }
+
+    #[test]
+    fn castling_rights_test() {
+        let board = castling_test_board();
+
+        let rook_moved = generator::create_new_board(&board, square(7, 0), square(7, 3));
+        assert_eq!(rook_moved.castling_availability, chess_structs::CASTLING_FULL & !chess_structs::WHITE_KINGSIDE);
+
+        let king_moved = generator::create_new_board(&board, square(4, 0), square(4, 1));
+        assert_eq!(king_moved.castling_availability, chess_structs::BLACK_KINGSIDE | chess_structs::BLACK_QUEENSIDE);
+
+        // capturing a rook in its corner takes away the opponent's right
+        let rook_captured = generator::create_new_board(&board, square(0, 0), square(0, 7));
+        assert_eq!(rook_captured.castling_availability, chess_structs::WHITE_KINGSIDE | chess_structs::BLACK_KINGSIDE);
+    }
+
+    #[test]
+    fn chess960_castling_test() {
+        // the king castles queenside from d1 onto c1, the rook from b1 onto the king's square
+        let fen = "4k3/8/8/8/8/8/8/1R1K3R w BH - 0 1";
+        let board = game_state::parse_fen(Color::Black, fen).ok().unwrap().board_state;
+        let moves: Vec<(Move, Board)> = KingItr::new(&board, square(3, 0)).filter(|(mv, _)| mv.is_castling()).collect();
+        let castling: Vec<String> = moves.iter().map(|(mv, _)| mv.to_string()).collect();
+        assert_eq!(castling, vec!["d1h1", "d1b1"]);
+        let queenside = &moves[1].1;
+        assert!(is_king(queenside, 2, 0));
+        assert_eq!(queenside.piece_at(square(3, 0)).map(|piece| piece.kind), Some(Kind::Rook));
+        assert!(queenside.piece_at(square(1, 0)).is_none());
+        assert_eq!(queenside.castling_availability, chess_structs::CASTLING_UNAVAILABLE);
+
+        // the king already stands on g1, only the rook moves
+        let fen = "4k3/8/8/8/8/8/8/6KR w K - 0 1";
+        let mut board = game_state::parse_fen(Color::Black, fen).ok().unwrap().board_state;
+        let kingside = KingItr::new(&board, square(6, 0)).find(|(mv, _)| mv.is_castling()).unwrap();
+        assert_eq!(kingside.0.to_string(), "g1h1");
+        assert!(is_king(&kingside.1, 6, 0));
+        assert_eq!(kingside.1.piece_at(square(5, 0)).map(|piece| piece.kind), Some(Kind::Rook));
+        let undo = board.make_move(&kingside.0);
+        board.unmake_move(undo);
+        assert_eq!(board, game_state::parse_fen(Color::Black, fen).ok().unwrap().board_state);
+
+        // the rook on b1 shields c1 from the rook on a1 until it castles
+        let fen = "4k3/8/8/8/8/8/8/rR1K4 w B - 0 1";
+        let board = game_state::parse_fen(Color::Black, fen).ok().unwrap().board_state;
+        assert!(!KingItr::new(&board, square(3, 0)).any(|(mv, _)| mv.is_castling()));
+    }
+
+    fn en_passant_test_board() -> Board {
+        let mut board = Board::new(Color::White, Some(square(3, 5)), chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(4, 4), Piece { kind: Kind::Pawn, color: Color::White });
+        board.put_piece(square(3, 4), Piece { kind: Kind::Pawn, color: Color::Black });
+        board.put_piece(square(7, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(0, 7), Piece { kind: Kind::King, color: Color::Black });
+        board
+    }
+
+    #[test]
+    fn en_passant_test() {
+        let board = en_passant_test_board();
+        let boards: Vec<Board> = PawnItr::new(&board, square(4, 4)).map(|(_, b)| b).collect();
+        // e6 and exd6
+        assert_eq!(boards.len(), 2);
+        let capture = boards.iter().find(|b| b.piece_at(square(3, 5)).is_some()).unwrap();
+        assert!(capture.piece_at(square(3, 4)).is_none());
+        assert!(capture.piece_at(square(4, 4)).is_none());
+        assert!(capture.en_passant.is_none());
+
+        // the pawn next to us did not just move two squares
+        let mut board = en_passant_test_board();
+        board.en_passant = None;
+        assert_eq!(PawnItr::new(&board, square(4, 4)).count(), 1);
+    }
+
+    #[test]
+    fn en_passant_horizontal_pin_test() {
+        // removing both pawns from the fifth rank would expose the king to the rook
+        let mut board = en_passant_test_board();
+        board.remove_piece(square(7, 0));
+        board.put_piece(square(0, 4), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(7, 4), Piece { kind: Kind::Rook, color: Color::Black });
+        let boards: Vec<Board> = PawnItr::new(&board, square(4, 4)).map(|(_, b)| b).collect();
+        assert_eq!(boards.len(), 1);
+        assert!(boards[0].piece_at(square(4, 5)).is_some());
+    }
+
+    #[test]
+    fn en_passant_square_test() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(4, 1), Piece { kind: Kind::Pawn, color: Color::White });
+        board.put_piece(square(3, 6), Piece { kind: Kind::Pawn, color: Color::Black });
+
+        let board = generator::create_new_board(&board, square(4, 1), square(4, 3));
+        assert_eq!(board.en_passant, Some(square(4, 2)));
+        let board = generator::create_new_board(&board, square(3, 6), square(3, 5));
+        assert_eq!(board.en_passant, None);
+    }
+
+    #[test]
+    fn promotion_test() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(1, 6), Piece { kind: Kind::Pawn, color: Color::White });
+        board.put_piece(square(0, 7), Piece { kind: Kind::Rook, color: Color::Black });
+        board.put_piece(square(4, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(3, 6), Piece { kind: Kind::King, color: Color::Black });
+
+        let boards: Vec<Board> = PawnItr::new(&board, square(1, 6)).map(|(_, b)| b).collect();
+        // b8 and bxa8, each to queen, rook, bishop and knight
+        assert_eq!(boards.len(), 8);
+        for x in 0..2 {
+            let kinds: Vec<Kind> = boards.iter()
+                .filter_map(|b| b.piece_at(square(x, 7)))
+                .filter(|piece| piece.color == Color::White)
+                .map(|piece| piece.kind)
+                .collect();
+            assert_eq!(kinds, vec![Kind::Queen, Kind::Rook, Kind::Bishop, Kind::Knight]);
+        }
+        assert!(boards.iter().all(|b| b.piece_at(square(1, 6)).is_none()));
+
+        // only the knight on b8 checks the king on d7
+        let checking: Vec<&Board> = boards.iter().filter(|b| b.checks & chess_structs::BLACK_IS_CHECKED > 0).collect();
+        assert_eq!(checking.len(), 1);
+        assert_eq!(checking[0].piece_at(square(1, 7)).map(|piece| piece.kind), Some(Kind::Knight));
+    }
+
+    #[test]
+    fn black_promotion_test() {
+        let mut board = Board::new(Color::Black, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(6, 1), Piece { kind: Kind::Pawn, color: Color::Black });
+        board.put_piece(square(0, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(7, 7), Piece { kind: Kind::King, color: Color::Black });
+
+        let kinds: Vec<Kind> = PawnItr::new(&board, square(6, 1))
+            .filter_map(|(_, b)| b.piece_at(square(6, 0)))
+            .map(|piece| piece.kind)
+            .collect();
+        assert_eq!(kinds, vec![Kind::Queen, Kind::Rook, Kind::Bishop, Kind::Knight]);
+    }
+
+    #[test]
+    fn move_test() {
+        let board = castling_test_board();
+        let moves: Vec<Move> = KingItr::new(&board, square(4, 0)).map(|(mv, _)| mv).collect();
+        let castling: Vec<String> = moves.iter().filter(|mv| mv.is_castling()).map(|mv| mv.to_string()).collect();
+        assert_eq!(castling, vec!["e1g1", "e1c1"]);
+        assert!(moves.iter().all(|mv| !mv.is_capture() && mv.promotion.is_none()));
+
+        let board = en_passant_test_board();
+        let moves: Vec<Move> = PawnItr::new(&board, square(4, 4)).map(|(mv, _)| mv).collect();
+        assert_eq!(moves.iter().map(|mv| mv.to_string()).collect::<Vec<String>>(), vec!["e5e6", "e5d6"]);
+        assert!(moves[1].is_capture() && moves[1].is_en_passant());
+
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(1, 6), Piece { kind: Kind::Pawn, color: Color::White });
+        board.put_piece(square(0, 7), Piece { kind: Kind::Rook, color: Color::Black });
+        let captures: Vec<String> = PawnItr::new(&board, square(1, 6))
+            .filter(|(mv, _)| mv.is_capture())
+            .map(|(mv, _)| mv.to_string())
+            .collect();
+        assert_eq!(captures, vec!["b7a8q", "b7a8r", "b7a8b", "b7a8n"]);
+    }
+
+    /// Plays every move down to `depth` plies in place, comparing against the generated boards on the way.
+    fn assert_make_unmake(board: &mut Board, depth: u32) {
+        if depth == 0 {
+            return;
+        }
+        let before = *board;
+        let successors: Vec<(Move, Board)> = board_stream!(before).collect();
+        for (mv, next_board) in successors {
+            let undo = board.make_move(&mv);
+            assert_eq!(*board, next_board, "make {}", mv);
+            assert_make_unmake(board, depth - 1);
+            board.unmake_move(undo);
+            assert_eq!(*board, before, "unmake {}", mv);
+        }
+    }
+
+    #[test]
+    fn make_unmake_test() {
+        // castling both ways, promotions with and without capture, en passant and captures of castling rooks
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ];
+        for fen in fens.iter() {
+            let mut board = game_state::map_from_libfen(Color::Black, fen_rs::parse(fen).ok().unwrap()).board_state;
+            assert_make_unmake(&mut board, 3);
+        }
+        assert_make_unmake(&mut en_passant_test_board(), 2);
+    }
+
+    #[test]
+    fn legal_moves_test() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = game_state::map_from_libfen(Color::Black, fen_rs::parse(fen).ok().unwrap()).board_state;
+        let moves = board.legal_moves();
+        assert_eq!(moves.len(), 48);
+        // the iterators hand out the same moves in the same order
+        let streamed: Vec<Move> = board_stream!(board).map(|(mv, _)| mv).collect();
+        assert_eq!(&moves[..], &streamed[..]);
+    }
+
+    #[test]
+    fn gen_type_test() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = game_state::map_from_libfen(Color::Black, fen_rs::parse(fen).ok().unwrap()).board_state;
+        let generate = |gen_type: GenType| {
+            let mut moves = MoveList::new();
+            generator::generate_moves(&board, gen_type, &mut moves);
+            moves
+        };
+        let (captures, quiets, tactical, all) = (generate(GenType::Captures), generate(GenType::Quiets), generate(GenType::Tactical), generate(GenType::All));
+        assert_eq!(captures.len(), 8);
+        assert_eq!(captures.len() + quiets.len(), all.len());
+        assert!(all.iter().all(|mv| captures.contains(mv) != quiets.contains(mv)));
+        // no quiet move gives check here
+        assert_eq!(&tactical[..], &captures[..]);
+    }
+
+    #[test]
+    fn tactical_test() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(0, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(0, 1), Piece { kind: Kind::Rook, color: Color::White });
+        board.put_piece(square(1, 6), Piece { kind: Kind::Pawn, color: Color::White });
+        board.put_piece(square(7, 7), Piece { kind: Kind::King, color: Color::Black });
+        board.put_piece(square(0, 6), Piece { kind: Kind::Pawn, color: Color::Black });
+
+        let mut moves = MoveList::new();
+        generator::generate_moves(&board, GenType::Tactical, &mut moves);
+        let moves: Vec<String> = moves.iter().map(|mv| mv.to_string()).collect();
+        // the check along the h-file, the capture, and every promotion whether it checks or not
+        assert_eq!(moves, vec!["a2h2", "a2a7", "b7b8q", "b7b8r", "b7b8b", "b7b8n"]);
+    }
+
+    #[test]
+    fn pin_test() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(4, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(4, 1), Piece { kind: Kind::Rook, color: Color::White });
+        board.put_piece(square(3, 1), Piece { kind: Kind::Bishop, color: Color::White });
+        board.put_piece(square(4, 6), Piece { kind: Kind::Rook, color: Color::Black });
+        board.put_piece(square(0, 4), Piece { kind: Kind::Bishop, color: Color::Black });
+        board.put_piece(square(7, 7), Piece { kind: Kind::King, color: Color::Black });
+
+        // the rook may move along the file up to the pinning rook, the bishop along the diagonal up to a5
+        let moves: Vec<String> = board.legal_moves().iter()
+            .filter(|mv| mv.from != square(4, 0))
+            .map(|mv| mv.to_string())
+            .collect();
+        assert_eq!(moves, vec!["d2c3", "d2b4", "d2a5", "e2e3", "e2e4", "e2e5", "e2e6", "e2e7"]);
+    }
+
+    #[test]
+    fn double_check_test() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::WHITE_IS_CHECKED);
+        board.put_piece(square(4, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(0, 0), Piece { kind: Kind::Queen, color: Color::White });
+        board.put_piece(square(4, 6), Piece { kind: Kind::Rook, color: Color::Black });
+        board.put_piece(square(3, 2), Piece { kind: Kind::Knight, color: Color::Black });
+        board.put_piece(square(7, 7), Piece { kind: Kind::King, color: Color::Black });
+
+        // the queen could take the knight or block the rook, but not both
+        let moves: Vec<String> = board.legal_moves().iter().map(|mv| mv.to_string()).collect();
+        assert_eq!(moves, vec!["e1d1", "e1f1", "e1d2"]);
+    }
+
+    #[test]
+    fn single_check_test() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::WHITE_IS_CHECKED);
+        board.put_piece(square(4, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(0, 3), Piece { kind: Kind::Queen, color: Color::White });
+        board.put_piece(square(4, 6), Piece { kind: Kind::Rook, color: Color::Black });
+        board.put_piece(square(7, 7), Piece { kind: Kind::King, color: Color::Black });
+
+        // the king steps off the file, or the queen blocks it
+        let moves: Vec<String> = board.legal_moves().iter().map(|mv| mv.to_string()).collect();
+        assert_eq!(moves, vec!["e1d1", "e1f1", "e1d2", "e1f2", "a4e4"]);
+    }
+
     #[test]
     fn test_checks_black_pawn() {
         let attacker_sq = Index2D {x: 3, y: 3};
@@ -789,9 +1182,9 @@ mod tests {
                     continue;
                 }
                 let mut board = Board::new(Color::White, None, chess_structs::CASTLING_FULL, chess_structs::NO_CHECKS);
-                board.squares[y][x] = Some(Piece { kind: Kind::King, color: Color::White });
-                board.squares[attacker_sq.y][attacker_sq.x] = Some(Piece { kind: Kind::Pawn, color: Color::Black });
-                board.squares[friendly_sq.y][friendly_sq.x] = Some(Piece { kind: Kind::Pawn, color: Color::White });
+                board.put_piece(square(x, y), Piece { kind: Kind::King, color: Color::White });
+                board.put_piece(attacker_sq.to_square(), Piece { kind: Kind::Pawn, color: Color::Black });
+                board.put_piece(friendly_sq.to_square(), Piece { kind: Kind::Pawn, color: Color::White });
                 let (is_white_checked, is_black_checked) = generator::checks(&board);
                 // black pawn attacks downwards + left/right; check if we're in its path
                 if y == attacker_sq.y - 1 && (x == attacker_sq.x - 1 || x == attacker_sq.x + 1) {
@@ -815,9 +1208,9 @@ mod tests {
                     continue;
                 }
                 let mut board = Board::new( Color::Black, None, chess_structs::CASTLING_FULL, chess_structs::NO_CHECKS);
-                board.squares[y][x] = Some(Piece { kind: Kind::King, color: Color::Black });
-                board.squares[attacker_sq.y][attacker_sq.x] = Some(Piece { kind: Kind::Pawn, color: Color::White });
-                board.squares[friendly_sq.y][friendly_sq.x] = Some(Piece { kind: Kind::Pawn, color: Color::Black });
+                board.put_piece(square(x, y), Piece { kind: Kind::King, color: Color::Black });
+                board.put_piece(attacker_sq.to_square(), Piece { kind: Kind::Pawn, color: Color::White });
+                board.put_piece(friendly_sq.to_square(), Piece { kind: Kind::Pawn, color: Color::Black });
                 let (is_white_checked, is_black_checked) = generator::checks(&board);
                 // white pawn attacks upwards + left/right; check if we're in its path
                 if y == attacker_sq.y + 1 && (x == attacker_sq.x - 1 || x == attacker_sq.x + 1) {
@@ -855,9 +1248,9 @@ mod tests {
                     continue;
                 }
                 let mut board = Board::new( Color::White, None, chess_structs::CASTLING_FULL, chess_structs::NO_CHECKS);
-                board.squares[y][x] = Some(Piece { kind: Kind::King, color: Color::White });
-                board.squares[attacker_sq.y][attacker_sq.x] = Some(Piece { kind: Kind::Knight, color: Color::Black });
-                board.squares[friendly_sq.y][friendly_sq.x] = Some(Piece { kind: Kind::Knight, color: Color::White });
+                board.put_piece(square(x, y), Piece { kind: Kind::King, color: Color::White });
+                board.put_piece(attacker_sq.to_square(), Piece { kind: Kind::Knight, color: Color::Black });
+                board.put_piece(friendly_sq.to_square(), Piece { kind: Kind::Knight, color: Color::White });
                 let (is_white_checked, is_black_checked) = generator::checks(&board);
                 if attacked_sqs.iter().any(|attacked_sq| new_sq == *attacked_sq) {
                     assert!(is_white_checked);
@@ -893,9 +1286,9 @@ mod tests {
                     continue;
                 }
                 let mut board = Board::new( Color::White, None, chess_structs::CASTLING_FULL, chess_structs::NO_CHECKS);
-                board.squares[y][x] = Some(Piece { kind: Kind::King, color: Color::White });
-                board.squares[attacker_sq.y][attacker_sq.x] = Some(Piece { kind: Kind::Rook, color: Color::Black });
-                board.squares[friendly_sq.y][friendly_sq.x] = Some(Piece { kind: Kind::Rook, color: Color::White });
+                board.put_piece(square(x, y), Piece { kind: Kind::King, color: Color::White });
+                board.put_piece(attacker_sq.to_square(), Piece { kind: Kind::Rook, color: Color::Black });
+                board.put_piece(friendly_sq.to_square(), Piece { kind: Kind::Rook, color: Color::White });
                 let (is_white_checked, is_black_checked) = generator::checks(&board);
                 if attacked_sqs.iter().any(|attacked_sq| new_sq == *attacked_sq) {
                     assert!(is_white_checked);
@@ -919,4 +1312,64 @@ mod tests {
     fn test_checks_block() {
         // test that friendly pieces will block checks
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn crazyhouse_test() {
+        // a pawn may not be dropped on the first or last rank
+        let mut board = game_state::parse_fen(Color::Black, "4Q~2k/8/5n2/8/8/8/8/4K3[P] w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Crazyhouse;
+        let drops: Vec<Move> = board.legal_moves().iter().filter(|mv| mv.is_drop()).copied().collect();
+        assert_eq!(drops.len(), 48 - 1);
+        assert!(drops.iter().all(|mv| mv.promotion == Some(Kind::Pawn) && mv.to >= 8 && mv.to < 56));
+        assert_eq!(drops[0].to_string(), "P@a2");
+
+        // the queen on e8 came from a promotion, so when the knight takes it, it goes into the pocket as a pawn
+        let mut board = game_state::parse_fen(Color::Black, "4Q~2k/8/5n2/8/8/8/8/4K3[P] b - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Crazyhouse;
+        let before = board;
+        let capture = Move::new(square(5, 5), square(4, 7), None, chess_structs::CAPTURE);
+        let undo = board.make_move(&capture);
+        assert_eq!(board.pockets[Color::Black.index()][Kind::Pawn.index()], 1);
+        assert_eq!(board.pockets[Color::Black.index()][Kind::Queen.index()], 0);
+        assert_eq!(board.promoted, 0);
+        board.unmake_move(undo);
+        assert_eq!(board, before);
+
+        // a drop in between is the only way out of the rook's check besides the king moving
+        let mut board = game_state::parse_fen(Color::Black, "4r2k/8/8/8/8/8/8/4K3[N] w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Crazyhouse;
+        let drops: Vec<String> = board.legal_moves().iter().filter(|mv| mv.is_drop()).map(|mv| mv.to_string()).collect();
+        assert_eq!(drops, vec!["N@e2", "N@e3", "N@e4", "N@e5", "N@e6", "N@e7"]);
+        assert_make_unmake(&mut board, 2);
+    }
+
+    #[test]
+    fn atomic_test() {
+        // the knight takes on d5 and blows up itself, the rook on c4 and the bishop on e5, but not the pawn on e6
+        let mut board = game_state::parse_fen(Color::Black, "4k3/8/4p3/3pb3/2R5/2N5/8/4K3 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Atomic;
+        generator::update_checks(&mut board);
+        let before = board;
+        let capture = *board.legal_moves().iter().find(|mv| mv.to_string() == "c3d5").unwrap();
+        let undo = board.make_move(&capture);
+        assert_eq!(board.occupied, bitboard::bit(square(4, 7)) | bitboard::bit(square(4, 5)) | bitboard::bit(square(4, 0)));
+        board.unmake_move(undo);
+        assert_eq!(board, before);
+        assert_make_unmake(&mut board, 2);
+
+        // neither the king nor the queen may take the rook, the explosion would take the white king along
+        let mut board = game_state::parse_fen(Color::Black, "4k3/8/8/8/8/8/3r4/3QK3 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Atomic;
+        generator::update_checks(&mut board);
+        let moves: Vec<String> = board.legal_moves().iter().map(|mv| mv.to_string()).collect();
+        assert!(!moves.contains(&"d1d2".to_string()));
+        assert!(!moves.contains(&"e1d2".to_string()));
+
+        // kings next to each other cannot be checked, so the white king may step onto a square the rook covers
+        let mut board = game_state::parse_fen(Color::Black, "8/8/8/8/8/3k4/3K3r/8 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Atomic;
+        generator::update_checks(&mut board);
+        assert_eq!(board.checks, chess_structs::NO_CHECKS);
+        assert!(board.legal_moves().iter().any(|mv| mv.to_string() == "d2e2"));
+    }
+}
@@ -1,4 +1,9 @@
 mod chess_structs;
+mod bitboard;
+mod attacks;
+mod move_list;
+mod move_picker;
+mod see;
 #[macro_use]
 mod generator;
 mod evaluator;
@@ -6,12 +11,23 @@ mod minimax;
 mod game_state;
 mod libmappings;
 mod args;
+mod perft;
+mod chess960;
+mod variant;
 
 use std::process;
-use args::FryArgs;
-use crate::chess_structs::{Board, Piece, Kind, Color};
+use std::io;
+use std::io::Write;
+use std::time::Instant;
+use args::{FryArgs, PerftArgs};
+use crate::chess_structs::{Board, Piece, Kind, Color, Move, Index2D};
 use crate::game_state::GameState;
 use crate::args::ArgError;
+use crate::minimax::SearchResult;
+use crate::variant::Variant;
+
+/// Typed instead of a move, lists the pieces that are en prise
+const CMD_HANGING: &str = "hanging";
 
 enum ExitCodes {
     InvalidArgument,
@@ -31,7 +47,7 @@ impl ExitCodes {
 
 fn main() {
 
-    let FryArgs {color: fry_color, load_file } = match args::parse_args() {
+    let FryArgs {color: fry_color, chess960, variant, perft } = match args::parse_args() {
         Ok(args) => args,
         Err(error) => {
             match error {
@@ -47,51 +63,123 @@ fn main() {
         }
     };
 
-    let fen_string = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-//    let fen_string = "r3k2r/p2ppp2/8/8/8/8/P2PP1PP/R1B1KB1R w KQkq - 0 1";
-    let fen_state_result = fen_rs::parse(fen_string);
-
-    if let Err(e) = fen_state_result {
-        fen_rs::print_error(e);
-        process::exit(ExitCodes::Error.code());
+    if let Some(perft_args) = perft {
+        run_perft(perft_args, variant);
+        return;
     }
 
-    let game_state = game_state::map_from_libfen(Color::Black, fen_state_result.unwrap());
-    let starting_board = game_state.board_state;
+//    let fen_string = "r3k2r/p2ppp2/8/8/8/8/P2PP1PP/R1B1KB1R w KQkq - 0 1";
+    let starting_fen = match chess960 {
+        Some(index) => chess960::start_position_fen(index),
+        None => String::from(variant.start_fen())
+    };
+    let starting_board = load_fen(&starting_fen, variant);
 
     starting_board.print();
 
     let mut board = starting_board;
-    let plies = 0; // half moves played
     loop {
         if board.turn == fry_color {
-            let mut read_buf = String::new();
-            print!("move> ");
-            let player_move = std::io::stdin().read_line(&mut read_buf);
-//                    board.turn = board.turn.invert();
-            if let Some(new_board) = minimax::search(&board) {
-                board = new_board;
+            if let Some(result) = minimax::search(&board) {
+                board.make_move(&result.best_move);
+                println!("fry plays {}", result.best_move);
+                print_expected_line(&result);
                 board.print();
-                print!("B");
             } else {
-                println!("no more legal moves");
+                if let Some(outcome) = minimax::outcome(&board) {
+                    println!("{}", outcome);
+                }
                 break;
             }
         }
         else {
+            if let Some(outcome) = minimax::outcome(&board) {
+                println!("{}", outcome);
+                break;
+            }
+            let legal_moves: Vec<(Move, Board)> = board_stream!(&board).collect();
+
             let mut read_buf = String::new();
             print!("move> ");
-            let player_move = std::io::stdin().read_line(&mut read_buf);
-//                    board.turn = board.turn.invert();
-            if let Some(new_board) = minimax::search(&board) {
-                board = new_board;
-                board.print();
-                print!("A");
-            } else {
-                println!("no more legal moves");
+            io::stdout().flush().unwrap();
+            if io::stdin().read_line(&mut read_buf).unwrap_or(0) == 0 {
                 break;
             }
+
+            let player_move = read_buf.trim();
+            if player_move == CMD_HANGING {
+                print_hanging(&board, board.turn, "your");
+                print_hanging(&board, fry_color, "fry's");
+                continue;
+            }
+            match legal_moves.iter().find(|(mv, _)| mv.to_string() == player_move) {
+                Some((_, new_board)) => {
+                    board = *new_board;
+                    board.print();
+                }
+                None => println!("illegal move: {} (expected e.g. e2e4, e7e8q, e1g1 or N@f3, or {})", player_move, CMD_HANGING)
+            }
+        }
+    }
+
+}
+
+/// The line fry expects from the search, e.g. "expected line: e2e4 e7e5 g1f3 (score 35, depth 6, 48213 nodes)"
+fn print_expected_line(result: &SearchResult) {
+    let line: Vec<String> = result.pv.iter().map(|mv| mv.to_string()).collect();
+    println!("expected line: {} (score {}, depth {}, {} nodes)", line.join(" "), result.score, result.depth, result.nodes);
+}
+
+/// Lists the pieces of `color` the opponent can win material from, e.g. "your hanging pieces: Nd4 Rb2"
+fn print_hanging(board: &Board, color: Color, whose: &str) {
+    let mut hanging = board.hanging_pieces(color);
+    let mut pieces = Vec::new();
+    while let Some(square) = bitboard::pop_lsb(&mut hanging) {
+        if let Some(piece) = board.piece_at(square) {
+            let kind = Piece { kind: piece.kind, color: Color::White }.to_char();
+            pieces.push(format!("{}{}", kind, Index2D::from_square(square)));
         }
     }
 
+    if pieces.is_empty() {
+        println!("{} hanging pieces: none", whose);
+    } else {
+        println!("{} hanging pieces: {}", whose, pieces.join(" "));
+    }
+}
+
+fn load_fen(fen_string: &str, variant: Variant) -> Board {
+    let fen_state_result = game_state::parse_fen(Color::Black, fen_string);
+
+    if let Err(e) = fen_state_result {
+        fen_rs::print_error(e);
+        process::exit(ExitCodes::Error.code());
+    }
+
+    let mut game_state = fen_state_result.unwrap();
+    game_state.board_state.variant = variant;
+    // checks depend on the rules, e.g. touching kings cannot check each other in atomic
+    generator::update_checks(&mut game_state.board_state);
+    game_state.board_state
+}
+
+fn run_perft(perft_args: PerftArgs, variant: Variant) {
+    let board = load_fen(&perft_args.fen, variant);
+    board.print();
+
+    let start = Instant::now();
+    let nodes = if perft_args.divide {
+        let moves = perft::divide(&board, perft_args.depth);
+        for (mv, nodes) in moves.iter() {
+            println!("{}: {}", mv, nodes);
+        }
+        println!("\nmoves: {}", moves.len());
+        moves.iter().map(|(_, nodes)| nodes).sum()
+    } else {
+        perft::perft(&board, perft_args.depth)
+    };
+    let elapsed = start.elapsed();
+
+    println!("nodes: {}", nodes);
+    println!("time: {} ms", elapsed.as_millis());
 }
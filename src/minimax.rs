@@ -1,88 +1,414 @@
-use crate::chess_structs::{Board, Color};
+use crate::chess_structs;
+use crate::chess_structs::{Board, Color, Move};
 use crate::evaluator;
+use crate::generator;
+use crate::move_picker::{MovePicker, Killers};
+use crate::variant::Variant;
 use std::cmp;
+use std::fmt;
 
-const INITIAL_DEPTH: i32 = 5;
+/// How many half moves the search looks ahead before the quiescence search takes over
+const DEPTH: i32 = 6;
 
-static mut NODES_VISITED: i64 = 0;
+/// The deepest ply the search reaches, the quiescence search included. The killer table is sized by it.
+pub const MAX_PLY: usize = 64;
 
-struct Evaluation<'a> {
-    board: &'a Board,
-    eval: i32
+/// The score for being checkmated on the board, from the point of view of the side that delivered it.
+/// A mate `ply` half moves into the search scores `MATE - ply`, so quicker mates are preferred and getting mated is put off.
+pub const MATE: i32 = 100_000;
+
+/// Beyond any score, so the initial search window takes in every move
+const INFINITY: i32 = MATE + 1;
+
+/// What the search found in a position
+#[derive(Clone, PartialEq, Debug)]
+pub struct SearchResult {
+    pub best_move: Move,
+    /// In centipawns, from the point of view of the side to move
+    pub score: i32,
+    /// The line of play the search expects, starting with `best_move`.
+    /// It ends where the quiescence search takes over or the game is over.
+    pub pv: Vec<Move>,
+    /// In half moves, without the quiescence search
+    pub depth: i32,
+    /// The positions visited, those of the quiescence search included
+    pub nodes: u64,
+}
+
+/// What is kept from one node of the search to the next
+struct Searcher {
+    killers: Killers,
+    nodes: u64,
+}
+
+/// Searches the position to a fixed depth. None if the side to move has no legal moves, i.e. the game is over.
+pub fn search(board: &Board) -> Option<SearchResult> {
+    let mut searcher = Searcher { killers: Killers::new(), nodes: 0 };
+    let mut board = *board;
+    let mut pv = Vec::new();
+    let score = searcher.negamax(&mut board, DEPTH, 0, -INFINITY, INFINITY, &mut pv);
+    let best_move = *pv.first()?;
+    Some(SearchResult { best_move, score, pv, depth: DEPTH, nodes: searcher.nodes })
+}
+
+/// How the game ended, once the side to move has no legal moves left
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Outcome {
+    /// The side that delivered the mate
+    Checkmate(Color),
+    Stalemate,
+    /// The side that gave the third check in three-check
+    ThirdCheck(Color),
+    /// The side whose king reached the center in king of the hill
+    KingOfTheHill(Color),
+    /// The side left without pieces or moves in antichess
+    NothingLeft(Color),
+    /// The side that blew up the enemy king in atomic
+    KingExploded(Color),
+    /// Black, having captured all of the horde
+    HordeCaptured(Color),
+    /// The side whose king reached the eighth rank first in racing kings
+    RaceWon(Color),
+    /// Both kings reached the eighth rank in racing kings
+    RaceDrawn,
 }
 
-impl<'a> Evaluation<'a> {
-    fn is_bested_by(&self, other: &Evaluation, who_played: Color) -> bool {
-        match who_played{
-            Color::White => other.eval > self.eval,
-            Color::Black => other.eval < self.eval
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Checkmate(Color::White) => write!(f, "checkmate, white wins"),
+            Outcome::Checkmate(Color::Black) => write!(f, "checkmate, black wins"),
+            Outcome::Stalemate => write!(f, "stalemate, draw"),
+            Outcome::ThirdCheck(Color::White) => write!(f, "third check, white wins"),
+            Outcome::ThirdCheck(Color::Black) => write!(f, "third check, black wins"),
+            Outcome::KingOfTheHill(Color::White) => write!(f, "king of the hill, white wins"),
+            Outcome::KingOfTheHill(Color::Black) => write!(f, "king of the hill, black wins"),
+            Outcome::NothingLeft(Color::White) => write!(f, "nothing left to play, white wins"),
+            Outcome::NothingLeft(Color::Black) => write!(f, "nothing left to play, black wins"),
+            Outcome::KingExploded(Color::White) => write!(f, "king exploded, white wins"),
+            Outcome::KingExploded(Color::Black) => write!(f, "king exploded, black wins"),
+            Outcome::HordeCaptured(Color::White) => write!(f, "horde captured, white wins"),
+            Outcome::HordeCaptured(Color::Black) => write!(f, "horde captured, black wins"),
+            Outcome::RaceWon(Color::White) => write!(f, "king reached the eighth rank, white wins"),
+            Outcome::RaceWon(Color::Black) => write!(f, "king reached the eighth rank, black wins"),
+            Outcome::RaceDrawn => write!(f, "both kings reached the eighth rank, draw"),
         }
     }
 }
 
-pub fn search(initial_board: &Board) -> Option<Board> {
-    unsafe { NODES_VISITED = 0; }
-    let moves: Vec<Board> = board_stream!(initial_board).collect();
-    let evals: Vec<Evaluation> = moves.iter().map(|board| Evaluation { board, eval: minimax(board, INITIAL_DEPTH, i32::min_value(), i32::max_value(), board.turn == Color::White) }).collect();
+/// None while the game goes on
+pub fn outcome(board: &Board) -> Option<Outcome> {
+    if let Some(winner) = board.variant_win() {
+        match board.variant {
+            Variant::ThreeCheck => Some(Outcome::ThirdCheck(winner)),
+            Variant::KingOfTheHill => Some(Outcome::KingOfTheHill(winner)),
+            Variant::Atomic => Some(Outcome::KingExploded(winner)),
+            Variant::Horde => Some(Outcome::HordeCaptured(winner)),
+            Variant::RacingKings => Some(Outcome::RaceWon(winner)),
+            Variant::Standard | Variant::Antichess | Variant::Crazyhouse => Some(Outcome::Checkmate(winner)),
+        }
+    } else if board.variant_draw() {
+        Some(Outcome::RaceDrawn)
+    } else if !board.legal_moves().is_empty() {
+        None
+    } else if board.variant == Variant::Antichess {
+        Some(Outcome::NothingLeft(board.turn))
+    } else if board.checks != chess_structs::NO_CHECKS {
+        Some(Outcome::Checkmate(board.turn.invert()))
+    } else {
+        Some(Outcome::Stalemate)
+    }
+}
 
-    unsafe { println!("moves computed: {}", NODES_VISITED); }
+/// The score of a position where the side to move has no legal moves, `ply` half moves into the search,
+/// from the point of view of the side to move. A win by the variant's rules scores like a mate.
+fn terminal_score(board: &Board, ply: i32) -> i32 {
+    // in antichess, having no moves left, e.g. for having no pieces left, wins
+    let winner = match board.variant_win() {
+        None if board.variant == Variant::Antichess => Some(board.turn),
+        winner => winner
+    };
+    match winner {
+        Some(winner) if winner == board.turn => MATE - ply,
+        Some(_) => -(MATE - ply),
+        None if board.checks != chess_structs::NO_CHECKS => -(MATE - ply),
+        None => 0
+    }
+}
 
-    let mut best_move: Option<Evaluation> = None;
+/// The static evaluation from the point of view of the side to move
+fn relative_eval(board: &Board) -> i32 {
+    match board.turn {
+        Color::White => evaluator::eval(board),
+        Color::Black => -evaluator::eval(board),
+    }
+}
 
-    for evaluation in evals {
-        let evaluation_ref = &evaluation;
-        println!("eval: {}", evaluation_ref.eval);
-        if best_move.is_none() {
-            println!("new best for {:?}: {}", initial_board.turn, evaluation_ref.eval);
-            best_move = Some(evaluation);
+impl Searcher {
+    /// The score of the position for the side to move, within the window `alpha` to `beta`.
+    /// `pv` is filled with the best line found from here, if any move scored above `alpha`.
+    /// `ply` counts the half moves played since the root.
+    /// Killers are kept per remaining depth, which in a fixed depth search stands for the ply.
+    fn negamax(&mut self, board: &mut Board, depth: i32, ply: i32, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        pv.clear();
+        if depth == 0 {
+            return self.quiesce(board, ply, true, alpha, beta);
         }
-        else if let Some(current_best) = &best_move {
-            if current_best.is_bested_by(evaluation_ref, initial_board.turn) {
-                println!("new best for {:?}: {}", initial_board.turn, evaluation_ref.eval);
-                best_move = Some(evaluation);
+        self.nodes += 1;
+
+        let mut best = -INFINITY;
+        let mut line = Vec::new();
+        let mut picker = MovePicker::new(None, self.killers.get(depth as usize));
+        while let Some(mv) = picker.next(board) {
+            let undo = board.make_move(&mv);
+            let score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut line);
+            board.unmake_move(undo);
+            best = cmp::max(score, best);
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(mv);
+                pv.extend_from_slice(&line);
+            }
+            if alpha >= beta {
+                store_killer(&mut self.killers, depth, mv);
+                break;
             }
         }
+        if best == -INFINITY {
+            return terminal_score(board, ply);
+        }
+        best
     }
 
-    if best_move.is_none() {
-        return None;
+    /// Plays out captures and promotions until the position is quiet, so no evaluation is taken halfway through an exchange.
+    /// The side to move may stand pat, i.e. take the static evaluation instead of capturing, unless it is in check.
+    /// Quiet checks are tried on the first ply only, the evasions from them are searched in full.
+    /// Evasions that check back, e.g. drops in crazyhouse, could go on for long, so the search stops at `MAX_PLY`.
+    fn quiesce(&mut self, board: &mut Board, ply: i32, include_checks: bool, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+
+        if board.is_variant_end() {
+            return terminal_score(board, ply);
+        }
+        if ply >= MAX_PLY as i32 {
+            return relative_eval(board);
+        }
+        // only the side to move can be in check
+        let in_check = board.checks != chess_structs::NO_CHECKS;
+        let mut picker = if in_check { MovePicker::new(None, [None, None]) } else { MovePicker::tactical(include_checks) };
+        // in check, the worst case is being mated right here. In antichess, there is no standing pat on a compulsory capture.
+        let mut best = if in_check {
+            terminal_score(board, ply)
+        } else if board.variant == Variant::Antichess && generator::can_capture(board) {
+            -INFINITY
+        } else {
+            relative_eval(board)
+        };
+        alpha = cmp::max(best, alpha);
+        while alpha < beta {
+            let mv = match picker.next(board) {
+                Some(mv) => mv,
+                None => break
+            };
+            let undo = board.make_move(&mv);
+            let score = -self.quiesce(board, ply + 1, false, -beta, -alpha);
+            board.unmake_move(undo);
+            best = cmp::max(score, best);
+            alpha = cmp::max(score, alpha);
+        }
+        best
     }
+}
 
-    return Some(best_move.unwrap().board.to_owned());
+fn store_killer(killers: &mut Killers, depth: i32, mv: Move) {
+    if !mv.is_capture() && (mv.promotion.is_none() || mv.is_drop()) {
+        killers.store(depth as usize, mv);
+    }
 }
 
-// idea: if minimax returns integer min or max, that means someone was out of moves.
-// detect check mate like that?
 
-fn minimax(board: &Board, depth: i32, mut alpha: i32, mut beta: i32, is_whites_turn: bool) -> i32 {
+mod tests {
+    use crate::chess_structs::{Board, Color};
+    use crate::game_state;
+    use crate::minimax;
+    use crate::evaluator;
+    use crate::generator;
+    use crate::minimax::{Outcome, Searcher, INFINITY, MATE, MAX_PLY};
+    use crate::move_list::MAX_MOVES;
+    use crate::move_picker::Killers;
+    use crate::variant::Variant;
 
-    if depth == 0 {
-        unsafe { NODES_VISITED += 1; }
-        return evaluator::eval(board);
+    fn board_from_fen(fen: &str) -> Board {
+        game_state::map_from_libfen(Color::Black, fen_rs::parse(fen).ok().unwrap()).board_state
     }
 
-    if is_whites_turn {
-        let mut max_eval = i32::min_value();
-        for next_board in board_stream!(board) {
-            let eval = minimax(&next_board, depth-1, alpha, beta, false);
-            max_eval = cmp::max(eval, max_eval);
-            alpha = cmp::max(eval, alpha);
-            if beta <= alpha {
-                break;
-            }
-        }
-        return max_eval;
-    }
-    else {
-        let mut min_eval = i32::max_value();
-        for next_board in board_stream!(board) {
-            let eval = minimax(&next_board, depth-1, alpha, beta, true);
-            min_eval = cmp::min(eval, min_eval);
-            beta = cmp::min(eval, beta);
-            if beta <= alpha {
-                break;
-            }
+    /// A board to play by the rules of `variant`, with the checks those rules give
+    fn variant_board(fen: &str, variant: Variant) -> Board {
+        let mut board = board_from_fen(fen);
+        board.variant = variant;
+        generator::update_checks(&mut board);
+        board
+    }
+
+    /// The board after the move the search picks
+    fn play_best(board: &Board) -> Board {
+        let mut board = *board;
+        let result = minimax::search(&board).unwrap();
+        board.make_move(&result.best_move);
+        board
+    }
+
+    #[test]
+    fn outcome_test() {
+        assert_eq!(minimax::outcome(&board_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")), None);
+        // fool's mate
+        let board = board_from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        assert_eq!(minimax::outcome(&board), Some(Outcome::Checkmate(Color::Black)));
+        let board = board_from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(minimax::outcome(&board), Some(Outcome::Stalemate));
+        assert!(minimax::search(&board).is_none());
+    }
+
+    #[test]
+    fn mate_in_one_test() {
+        let board = board_from_fen("7k/8/6K1/8/8/8/8/R7 w - - 0 1");
+        let result = minimax::search(&board).unwrap();
+        assert_eq!(result.best_move.to_string(), "a1a8");
+        assert_eq!(result.score, MATE - 1);
+        assert_eq!(result.pv, vec![result.best_move]);
+        assert_eq!(minimax::outcome(&play_best(&board)), Some(Outcome::Checkmate(Color::White)));
+    }
+
+    #[test]
+    fn quiescence_ply_cap_test() {
+        // in check, but too deep to search the evasions
+        let mut board = board_from_fen("4k3/8/8/8/8/8/8/Q3K2r w - - 0 1");
+        let mut searcher = Searcher { killers: Killers::new(), nodes: 0 };
+        let score = searcher.quiesce(&mut board, MAX_PLY as i32, true, -MATE, MATE);
+        assert_eq!(score, evaluator::eval(&board));
+        assert_eq!(searcher.nodes, 1);
+    }
+
+    #[test]
+    fn full_pocket_test() {
+        // more drops than fit on the stack, searched down to the deepest ply. N@f7 and others mate.
+        let mut board = game_state::parse_fen(Color::Black, "6rk/6pp/8/8/8/8/8/4K3[QRBNP] w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Crazyhouse;
+        assert!(board.legal_moves().len() > MAX_MOVES);
+        let mut searcher = Searcher { killers: Killers::new(), nodes: 0 };
+        let mut pv = Vec::new();
+        let score = searcher.negamax(&mut board, 2, MAX_PLY as i32 - 2, -INFINITY, INFINITY, &mut pv);
+        assert_eq!(score, MATE - (MAX_PLY as i32 - 1));
+        assert!(board.is_legal_move(&pv[0]));
+    }
+
+    #[test]
+    fn principal_variation_test() {
+        // mate in two, the black king has to step to a7 first
+        let mut board = board_from_fen("k7/8/2K5/8/8/8/8/1R6 w - - 0 1");
+        let result = minimax::search(&board).unwrap();
+        assert_eq!(result.score, MATE - 3);
+        assert_eq!(result.pv.len(), 3);
+        assert_eq!(result.pv[0], result.best_move);
+        assert!(result.nodes > 0);
+        for mv in result.pv.iter() {
+            assert!(board.is_legal_move(mv));
+            board.make_move(mv);
         }
-        return min_eval;
+        assert_eq!(minimax::outcome(&board), Some(Outcome::Checkmate(Color::White)));
+
+        // black to move scores from its own point of view
+        let board = board_from_fen("6k1/8/8/8/8/8/1r6/K7 b - - 0 1");
+        assert!(minimax::search(&board).unwrap().score > 0);
+    }
+
+    #[test]
+    fn avoid_stalemate_test() {
+        // a queen up, but most queen moves that take the last squares from the king are stalemate
+        let board = board_from_fen("7k/8/6K1/8/8/8/8/5Q2 w - - 0 1");
+        let board = play_best(&board);
+        assert_ne!(minimax::outcome(&board), Some(Outcome::Stalemate));
+    }
+
+    #[test]
+    fn third_check_test() {
+        // any check wins at once, even one that loses the rook
+        let mut board = game_state::parse_fen(Color::Black, "4k3/8/8/8/8/8/q7/4K2R w - - 0 1 +2+0").ok().unwrap().board_state;
+        board.variant = Variant::ThreeCheck;
+        let board = play_best(&board);
+        assert_eq!(minimax::outcome(&board), Some(Outcome::ThirdCheck(Color::White)));
+        assert!(minimax::search(&board).is_none());
+    }
+
+    #[test]
+    fn king_of_the_hill_test() {
+        // stepping onto d4 or e4 wins at once, the black rook guards d5 and e5
+        let mut board = board_from_fen("k7/8/8/5r2/8/4K3/8/7R w - - 0 1");
+        board.variant = Variant::KingOfTheHill;
+        let board = play_best(&board);
+        assert_eq!(minimax::outcome(&board), Some(Outcome::KingOfTheHill(Color::White)));
+    }
+
+    #[test]
+    fn atomic_test() {
+        // taking the queen next to the black king blows both up
+        let board = variant_board("3qk3/8/8/8/8/8/8/3RK3 w - - 0 1", Variant::Atomic);
+        let result = minimax::search(&board).unwrap();
+        assert_eq!(result.best_move.to_string(), "d1d8");
+        assert_eq!(minimax::outcome(&play_best(&board)), Some(Outcome::KingExploded(Color::White)));
+    }
+
+    #[test]
+    fn horde_test() {
+        // the queen takes the last pawn of the horde
+        let mut board = board_from_fen("4k3/8/8/8/8/8/3P4/2q5 b - - 0 1");
+        board.variant = Variant::Horde;
+        assert_eq!(minimax::outcome(&board), None);
+        let board = play_best(&board);
+        assert_eq!(minimax::outcome(&board), Some(Outcome::HordeCaptured(Color::Black)));
+    }
+
+    #[test]
+    fn racing_kings_test() {
+        let mut board = board_from_fen(Variant::RacingKings.start_fen());
+        board.variant = Variant::RacingKings;
+        assert_eq!(minimax::outcome(&board), None);
+
+        let mut board = board_from_fen("8/2K5/8/8/8/8/8/k7 w - - 0 1");
+        board.variant = Variant::RacingKings;
+        let board = play_best(&board);
+        assert_eq!(minimax::outcome(&board), Some(Outcome::RaceWon(Color::White)));
+
+        let mut board = board_from_fen("2K5/5k2/8/8/8/8/8/8 b - - 0 1");
+        board.variant = Variant::RacingKings;
+        let board = play_best(&board);
+        assert_eq!(minimax::outcome(&board), Some(Outcome::RaceDrawn));
+    }
+
+    #[test]
+    fn antichess_quiescence_test() {
+        // white stands badly with the most material left, but has to take the pawn, and the rook has to take the queen back
+        let mut board = board_from_fen("8/8/8/2r5/8/2p5/8/2Q5 w - - 0 1");
+        board.variant = Variant::Antichess;
+        assert!(evaluator::eval(&board) < 0);
+        let mut searcher = Searcher { killers: Killers::new(), nodes: 0 };
+        assert!(searcher.quiesce(&mut board, 0, false, -MATE, MATE) > 0);
+
+        // taking the last black piece leaves white worse off, but standing pat is not allowed
+        let mut board = board_from_fen("8/8/8/8/8/2p5/8/2Q5 w - - 0 1");
+        board.variant = Variant::Antichess;
+        assert!(searcher.quiesce(&mut board, 0, false, -MATE, MATE) < evaluator::eval(&board));
+    }
+
+    #[test]
+    fn antichess_test() {
+        // the last black pawn steps in front of the queen, which has to take it
+        let mut board = board_from_fen("8/8/8/8/8/2p5/8/1Q6 b - - 0 1");
+        board.variant = Variant::Antichess;
+        assert_eq!(minimax::outcome(&board), None);
+        let board = play_best(&board);
+        let board = play_best(&board);
+        assert_eq!(minimax::outcome(&board), Some(Outcome::NothingLeft(Color::Black)));
     }
 }
@@ -0,0 +1,87 @@
+use std::ops::{Deref, DerefMut};
+use crate::chess_structs;
+use crate::chess_structs::Move;
+
+/// More than the most moves any reachable position has in chess (218)
+pub const MAX_MOVES: usize = 256;
+
+/// A fixed capacity list of moves that lives on the stack, so generating moves does not allocate.
+/// Crazyhouse positions with full pockets can have more moves, those lists move to the heap once the stack space is full.
+/// Derefs to a slice of the moves pushed so far.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+    /// All of the moves, once there are more than `MAX_MOVES`
+    spilled: Vec<Move>,
+}
+
+impl MoveList {
+    pub fn new() -> MoveList {
+        MoveList {
+            moves: [Move::new(0, 0, None, chess_structs::QUIET_MOVE); MAX_MOVES],
+            len: 0,
+            spilled: Vec::new()
+        }
+    }
+
+    pub fn push(&mut self, mv: Move) {
+        if self.len < MAX_MOVES {
+            self.moves[self.len] = mv;
+        } else {
+            if self.spilled.is_empty() {
+                self.spilled.extend_from_slice(&self.moves);
+            }
+            self.spilled.push(mv);
+        }
+        self.len += 1;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> MoveList {
+        MoveList::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        if self.len > MAX_MOVES { &self.spilled } else { &self.moves[..self.len] }
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        if self.len > MAX_MOVES { &mut self.spilled } else { &mut self.moves[..self.len] }
+    }
+}
+
+
+mod tests {
+    use crate::chess_structs;
+    use crate::chess_structs::{Move, Kind};
+    use crate::move_list::{MoveList, MAX_MOVES};
+
+    #[test]
+    fn push_test() {
+        let mut moves = MoveList::new();
+        assert!(moves.is_empty());
+        moves.push(Move::new(12, 28, None, chess_structs::QUIET_MOVE));
+        moves.push(Move::new(52, 60, Some(Kind::Queen), chess_structs::CAPTURE));
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves.iter().map(|mv| mv.to_string()).collect::<Vec<String>>(), vec!["e2e4", "e7e8q"]);
+    }
+
+    #[test]
+    fn spill_test() {
+        let mut moves = MoveList::new();
+        for to in 0..MAX_MOVES + 2 {
+            moves.push(Move::new(0, to % 64, None, chess_structs::QUIET_MOVE));
+        }
+        assert_eq!(moves.len(), MAX_MOVES + 2);
+        assert_eq!(moves[MAX_MOVES - 1].to, (MAX_MOVES - 1) % 64);
+        assert_eq!(moves[MAX_MOVES + 1].to, (MAX_MOVES + 1) % 64);
+    }
+}
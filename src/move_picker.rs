@@ -0,0 +1,301 @@
+use crate::chess_structs::{Board, Kind, Move};
+use crate::generator;
+use crate::generator::GenType;
+use crate::minimax::MAX_PLY;
+use crate::move_list::MoveList;
+use crate::variant::Variant;
+
+/// Quiet moves that caused a beta cutoff, two per ply. A move that refutes one position
+/// often refutes its siblings as well, so these are tried before the other quiet moves.
+pub struct Killers([[Option<Move>; 2]; MAX_PLY]);
+
+impl Killers {
+    pub fn new() -> Killers {
+        Killers([[None; 2]; MAX_PLY])
+    }
+
+    pub fn get(&self, ply: usize) -> [Option<Move>; 2] {
+        self.0[ply]
+    }
+
+    /// Remembers a quiet move that caused a cutoff, replacing the older of the two killers
+    pub fn store(&mut self, ply: usize, mv: Move) {
+        let killers = &mut self.0[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+    }
+}
+
+impl Default for Killers {
+    fn default() -> Killers {
+        Killers::new()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Stage {
+    HashMove,
+    GenerateCaptures,
+    Captures,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Hands out the legal moves of a position in the order most likely to cause a cutoff: the hash move,
+/// captures and promotions (most valuable victim first), the killers, the remaining quiet moves, and finally
+/// the captures that lose material by static exchange evaluation.
+/// A stage is only generated once the previous one is used up, so a cutoff early on saves generating the quiet moves.
+///
+/// The board is passed to every call of `next` instead of being held, since the search plays the moves on it in between.
+pub struct MovePicker {
+    /// What the captures stage generates
+    noisy: GenType,
+    /// Stop after the captures stage, as the quiescence search does
+    noisy_only: bool,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    stage: Stage,
+    moves: MoveList,
+    /// The next move of `moves`, or the next killer, to hand out
+    index: usize,
+    /// Captures put off until after the quiet moves
+    bad_captures: MoveList,
+}
+
+impl MovePicker {
+    pub fn new(hash_move: Option<Move>, killers: [Option<Move>; 2]) -> MovePicker {
+        MovePicker {
+            noisy: GenType::Captures,
+            noisy_only: false,
+            hash_move,
+            killers,
+            stage: Stage::HashMove,
+            moves: MoveList::new(),
+            index: 0,
+            bad_captures: MoveList::new()
+        }
+    }
+
+    /// Only the captures and promotions, and the quiet checks if asked for, most valuable victim first.
+    /// Moves that lose material by static exchange evaluation are left out.
+    pub fn tactical(include_checks: bool) -> MovePicker {
+        MovePicker {
+            noisy: if include_checks { GenType::Tactical } else { GenType::Captures },
+            noisy_only: true,
+            stage: Stage::GenerateCaptures,
+            ..MovePicker::new(None, [None, None])
+        }
+    }
+
+    /// The next move to search, or None once all legal moves were handed out.
+    /// `board` has to be in the position the picker was created for.
+    pub fn next(&mut self, board: &Board) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    match self.hash_move {
+                        Some(mv) if board.is_legal_move(&mv) => return Some(mv),
+                        _ => self.hash_move = None
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    self.generate(board, self.noisy);
+                    self.moves.sort_unstable_by_key(|mv| -capture_score(board, mv));
+                    self.stage = Stage::Captures;
+                }
+                // losing material is the aim in antichess, so no capture is put off there
+                Stage::Captures => match self.next_generated() {
+                    Some(mv) if board.variant == Variant::Antichess || board.see(&mv) >= 0 => return Some(mv),
+                    Some(mv) => if !self.noisy_only {
+                        self.bad_captures.push(mv);
+                    },
+                    None if self.noisy_only => self.stage = Stage::Done,
+                    None => {
+                        self.stage = Stage::Killers;
+                        self.index = 0;
+                    }
+                },
+                Stage::Killers => {
+                    let killer = match self.killers.get(self.index) {
+                        Some(&killer) => killer,
+                        None => {
+                            self.stage = Stage::GenerateQuiets;
+                            continue;
+                        }
+                    };
+                    self.index += 1;
+                    // a killer comes from a sibling position, where it may have been quiet but not here.
+                    // The generated move would differ in its flags then, so it fails the legality test.
+                    match killer {
+                        Some(mv) if Some(mv) != self.hash_move && board.is_legal_move(&mv) => return Some(mv),
+                        _ => self.killers[self.index - 1] = None
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    self.generate(board, GenType::Quiets);
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.next_generated() {
+                    Some(mv) => return Some(mv),
+                    None => {
+                        self.stage = Stage::BadCaptures;
+                        self.index = 0;
+                    }
+                },
+                Stage::BadCaptures => match self.bad_captures.get(self.index) {
+                    Some(&mv) => {
+                        self.index += 1;
+                        return Some(mv);
+                    }
+                    None => self.stage = Stage::Done
+                },
+                Stage::Done => return None
+            }
+        }
+    }
+
+    fn generate(&mut self, board: &Board, gen_type: GenType) {
+        self.moves = MoveList::new();
+        generator::generate_moves(board, gen_type, &mut self.moves);
+        self.index = 0;
+    }
+
+    /// The next generated move that was not handed out in an earlier stage already
+    fn next_generated(&mut self) -> Option<Move> {
+        while let Some(&mv) = self.moves.get(self.index) {
+            self.index += 1;
+            if Some(mv) != self.hash_move && !self.killers.contains(&Some(mv)) {
+                return Some(mv);
+            }
+        }
+        None
+    }
+}
+
+/// Most valuable victim, least valuable attacker. A promotion counts as capturing the piece it turns into,
+/// and a quiet check, drops included, as capturing nothing.
+fn capture_score(board: &Board, mv: &Move) -> i32 {
+    if mv.is_drop() {
+        return 0;
+    }
+    let victim = if mv.is_en_passant() {
+        Kind::Pawn.value()
+    } else if mv.is_castling() {
+        0
+    } else {
+        board.piece_at(mv.to).map_or(0, |piece| piece.kind.value())
+    };
+    let promotion = mv.promotion.map_or(0, |kind| kind.value());
+    let attacker = board.piece_at(mv.from).map_or(0, |piece| piece.kind.value());
+    (victim + promotion) * 100 - attacker
+}
+
+
+mod tests {
+    use crate::chess_structs;
+    use crate::chess_structs::{Board, Color, Kind, Move, Piece, square};
+    use crate::game_state;
+    use crate::move_picker::{MovePicker, Killers};
+    use crate::variant::Variant;
+
+    fn picked_moves(board: &Board, hash_move: Option<Move>, killers: [Option<Move>; 2]) -> Vec<Move> {
+        let mut picker = MovePicker::new(hash_move, killers);
+        let mut moves = Vec::new();
+        while let Some(mv) = picker.next(board) {
+            moves.push(mv);
+        }
+        moves
+    }
+
+    #[test]
+    fn picks_every_move_once() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = game_state::map_from_libfen(Color::Black, fen_rs::parse(fen).ok().unwrap()).board_state;
+        let legal = board.legal_moves();
+
+        let hash_move = legal.iter().find(|mv| mv.to_string() == "e1g1").copied();
+        let killer = legal.iter().find(|mv| mv.to_string() == "a2a3").copied();
+        // a killer that is not legal here is skipped
+        let illegal = Some(Move::new(square(0, 0), square(0, 5), None, chess_structs::QUIET_MOVE));
+        let picked = picked_moves(&board, hash_move, [killer, illegal]);
+
+        assert_eq!(picked.len(), legal.len());
+        assert!(legal.iter().all(|mv| picked.contains(mv)));
+        assert_eq!(picked[0], hash_move.unwrap());
+        // then the captures that do not lose material, the killer before the other quiet moves,
+        // and the remaining of the 8 captures at the end
+        let good_captures = legal.iter().filter(|mv| mv.is_capture() && board.see(mv) >= 0).count();
+        let bad_captures = 8 - good_captures;
+        let quiets_end = picked.len() - bad_captures;
+        assert!(good_captures > 0 && bad_captures > 0);
+        assert!(picked[1..1 + good_captures].iter().all(|mv| mv.is_capture()));
+        assert_eq!(picked[1 + good_captures], killer.unwrap());
+        assert!(picked[2 + good_captures..quiets_end].iter().all(|mv| !mv.is_capture()));
+        assert!(picked[quiets_end..].iter().all(|mv| mv.is_capture() && board.see(mv) < 0));
+    }
+
+    #[test]
+    fn captures_by_victim_value() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(0, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(7, 6), Piece { kind: Kind::King, color: Color::Black });
+        board.put_piece(square(3, 3), Piece { kind: Kind::Queen, color: Color::White });
+        board.put_piece(square(6, 3), Piece { kind: Kind::Pawn, color: Color::Black });
+        board.put_piece(square(2, 4), Piece { kind: Kind::Pawn, color: Color::White });
+        board.put_piece(square(3, 5), Piece { kind: Kind::Rook, color: Color::Black });
+
+        let captures: Vec<String> = picked_moves(&board, None, [None, None]).iter()
+            .filter(|mv| mv.is_capture())
+            .map(|mv| mv.to_string())
+            .collect();
+        // the rook before the pawn, and taking the rook with the pawn before taking it with the queen
+        assert_eq!(captures, vec!["c5d6", "d4d6", "d4g4"]);
+    }
+
+    #[test]
+    fn losing_captures_last() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(0, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(7, 6), Piece { kind: Kind::King, color: Color::Black });
+        board.put_piece(square(3, 3), Piece { kind: Kind::Queen, color: Color::White });
+        board.put_piece(square(3, 5), Piece { kind: Kind::Pawn, color: Color::Black });
+        board.put_piece(square(2, 6), Piece { kind: Kind::Pawn, color: Color::Black });
+
+        // the pawn on d6 is defended, taking it costs the queen
+        let picked = picked_moves(&board, None, [None, None]);
+        assert_eq!(picked.last().unwrap().to_string(), "d4d6");
+        assert_eq!(picked.iter().filter(|mv| mv.is_capture()).count(), 1);
+
+        let mut picker = MovePicker::tactical(false);
+        assert_eq!(picker.next(&board), None);
+    }
+
+    #[test]
+    fn antichess_losing_captures_test() {
+        // the queen takes the defended pawn, which SEE calls a loss
+        let mut board = game_state::parse_fen(Color::Black, "8/8/8/2r5/8/2p5/8/2Q5 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Antichess;
+        let mut picker = MovePicker::tactical(false);
+        assert_eq!(picker.next(&board).map(|mv| mv.to_string()), Some(String::from("c1c3")));
+        assert_eq!(picker.next(&board), None);
+    }
+
+    #[test]
+    fn killers_test() {
+        let mut killers = Killers::new();
+        let first = Move::new(square(4, 1), square(4, 3), None, chess_structs::QUIET_MOVE);
+        let second = Move::new(square(3, 1), square(3, 3), None, chess_structs::QUIET_MOVE);
+        killers.store(2, first);
+        killers.store(2, first);
+        assert_eq!(killers.get(2), [Some(first), None]);
+        killers.store(2, second);
+        assert_eq!(killers.get(2), [Some(second), Some(first)]);
+        assert_eq!(killers.get(3), [None, None]);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::chess_structs::{Board, Move};
+
+/// Counts the leaf nodes of the move tree `depth` plies below `board`.
+/// Compare against published perft results to find move generator bugs.
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    count_nodes(&mut board.clone(), depth)
+}
+
+/// Like `perft`, but split up per root move. Useful for narrowing down
+/// which move a wrong node count comes from.
+pub fn divide(board: &Board, depth: u32) -> Vec<(Move, u64)> {
+    let mut board = *board;
+    board.legal_moves().iter()
+        .map(|mv| {
+            let undo = board.make_move(mv);
+            let nodes = if depth > 0 { count_nodes(&mut board, depth - 1) } else { 1 };
+            board.unmake_move(undo);
+            (*mv, nodes)
+        })
+        .collect()
+}
+
+fn count_nodes(board: &mut Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = board.legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mv in moves.iter() {
+        let undo = board.make_move(mv);
+        nodes += count_nodes(board, depth - 1);
+        board.unmake_move(undo);
+    }
+    nodes
+}
+
+mod tests {
+    use crate::chess_structs::Color;
+    use crate::game_state;
+    use crate::perft::{perft, divide};
+    use crate::variant::Variant;
+
+    fn perft_fen(fen: &str, depth: u32) -> u64 {
+        let board = game_state::parse_fen(Color::Black, fen).ok().unwrap().board_state;
+        perft(&board, depth)
+    }
+
+    #[test]
+    fn perft_initial_position() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(perft_fen(fen, 1), 20);
+        assert_eq!(perft_fen(fen, 2), 400);
+        assert_eq!(perft_fen(fen, 3), 8902);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(perft_fen(fen, 1), 48);
+        assert_eq!(perft_fen(fen, 2), 2039);
+        assert_eq!(perft_fen(fen, 3), 97862);
+    }
+
+    #[test]
+    fn perft_position_3() {
+        let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+        assert_eq!(perft_fen(fen, 1), 14);
+        assert_eq!(perft_fen(fen, 2), 191);
+        assert_eq!(perft_fen(fen, 3), 2812);
+        assert_eq!(perft_fen(fen, 4), 43238);
+    }
+
+    #[test]
+    fn perft_chess960() {
+        // castling rights as Shredder-FEN, the king castles onto the squares it and the rooks start from
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        assert_eq!(perft_fen(fen, 1), 21);
+        assert_eq!(perft_fen(fen, 2), 528);
+        assert_eq!(perft_fen(fen, 3), 12189);
+
+        let fen = "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9";
+        assert_eq!(perft_fen(fen, 3), 18002);
+    }
+
+    #[test]
+    fn perft_antichess() {
+        let mut board = game_state::parse_fen(Color::Black, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Antichess;
+        assert_eq!(perft(&board, 3), 8067);
+    }
+
+    #[test]
+    fn perft_atomic() {
+        let mut board = game_state::parse_fen(Color::Black, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Atomic;
+        assert_eq!(perft(&board, 3), 8902);
+        assert_eq!(perft(&board, 4), 197326);
+    }
+
+    #[test]
+    fn perft_horde() {
+        let mut board = game_state::parse_fen(Color::Black, Variant::Horde.start_fen()).ok().unwrap().board_state;
+        board.variant = Variant::Horde;
+        assert_eq!(perft(&board, 3), 1274);
+        assert_eq!(perft(&board, 4), 23310);
+
+        let mut board = game_state::parse_fen(Color::Black, "k7/5p2/4p2P/3p2P1/2p2P2/1p2P2P/p2P2P1/2P2P2 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Horde;
+        assert_eq!(perft(&board, 4), 33781);
+    }
+
+    #[test]
+    fn perft_racing_kings() {
+        let mut board = game_state::parse_fen(Color::Black, Variant::RacingKings.start_fen()).ok().unwrap().board_state;
+        board.variant = Variant::RacingKings;
+        assert_eq!(perft(&board, 3), 11264);
+        assert_eq!(perft(&board, 4), 296242);
+
+        // with the white king one step from the goal, black may still draw level
+        let mut board = game_state::parse_fen(Color::Black, "4brn1/2K2k2/8/8/8/8/8/8 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::RacingKings;
+        assert_eq!(perft(&board, 5), 12981);
+    }
+
+    #[test]
+    fn perft_crazyhouse() {
+        let mut board = game_state::parse_fen(Color::Black, "2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Crazyhouse;
+        assert_eq!(perft(&board, 1), 301);
+        assert_eq!(perft(&board, 2), 75353);
+    }
+
+    #[test]
+    fn perft_position_4() {
+        let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        assert_eq!(perft_fen(fen, 1), 6);
+        assert_eq!(perft_fen(fen, 2), 264);
+        assert_eq!(perft_fen(fen, 3), 9467);
+    }
+
+    #[test]
+    fn perft_position_5() {
+        let fen = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+        assert_eq!(perft_fen(fen, 1), 44);
+        assert_eq!(perft_fen(fen, 2), 1486);
+        assert_eq!(perft_fen(fen, 3), 62379);
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = game_state::map_from_libfen(Color::Black, fen_rs::parse(fen).ok().unwrap()).board_state;
+        let moves = divide(&board, 2);
+        assert_eq!(moves.len(), 48);
+        assert_eq!(moves.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+    }
+}
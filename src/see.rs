@@ -0,0 +1,200 @@
+use crate::attacks;
+use crate::bitboard;
+use crate::chess_structs;
+use crate::chess_structs::{Board, Bitboard, Color, Kind, Move, Square};
+use crate::variant::Variant;
+
+impl Board {
+    /// Static exchange evaluation: the material the side making the capture `mv` wins, in centipawns,
+    /// if both sides keep recapturing on the target square with their least valuable piece for as long as it pays.
+    /// Sliders lined up behind a capturing piece join in once it has moved out of the way.
+    /// Pins and checks are not looked at.
+    /// In atomic there is nothing left to recapture, the capture is worth what its explosion takes from either side.
+    pub fn see(&self, mv: &Move) -> i32 {
+        if mv.is_castling() {
+            return 0;
+        }
+        let mover = match self.piece_at(mv.from) {
+            Some(piece) => piece,
+            None => return 0
+        };
+        if self.variant == Variant::Atomic {
+            return explosion_value(self, mv, mover.color);
+        }
+
+        let mut occupied = self.occupied & !bitboard::bit(mv.from);
+        let victim = if mv.is_en_passant() {
+            let captured = match mover.color {
+                Color::White => mv.to - 8,
+                Color::Black => mv.to + 8,
+            };
+            occupied &= !bitboard::bit(captured);
+            Kind::Pawn.value()
+        } else {
+            self.piece_at(mv.to).map_or(0, |piece| piece.kind.value())
+        };
+
+        // gains[i] is what the side making the i-th capture has won so far, assuming the piece it captured with is lost
+        let mut gains = [0; 32];
+        gains[0] = victim;
+        let mut on_square = match mv.promotion {
+            Some(kind) => {
+                gains[0] += kind.value() - Kind::Pawn.value();
+                kind.value()
+            }
+            None => mover.kind.value()
+        };
+
+        let mut depth = 0;
+        let mut side = mover.color;
+        let mut attackers = self.all_attackers_of(mv.to, occupied);
+        while depth + 1 < gains.len() {
+            side = side.invert();
+            let (square, kind) = match least_valuable(self, attackers & self.colors[side.index()], side) {
+                Some(attacker) => attacker,
+                None => break
+            };
+            // the king may only capture if nothing can capture it back
+            if kind == Kind::King && attackers & self.colors[side.invert().index()] != bitboard::EMPTY {
+                break;
+            }
+
+            depth += 1;
+            gains[depth] = on_square - gains[depth - 1];
+            on_square = kind.value();
+            occupied &= !bitboard::bit(square);
+            attackers = self.all_attackers_of(mv.to, occupied);
+        }
+
+        // each side may also stop recapturing, if that loses less
+        while depth > 0 {
+            gains[depth - 1] = -std::cmp::max(-gains[depth - 1], gains[depth]);
+            depth -= 1;
+        }
+        gains[0]
+    }
+
+    /// The pieces of `color` that the opponent can win material from by capturing them
+    pub fn hanging_pieces(&self, color: Color) -> Bitboard {
+        let mut hanging = bitboard::EMPTY;
+        let mut pieces = self.colors[color.index()] & !self.bitboard(color, Kind::King);
+        while let Some(square) = bitboard::pop_lsb(&mut pieces) {
+            if let Some((attacker, _)) = least_valuable(self, self.attackers_of(square, color.invert()), color.invert()) {
+                let capture = Move::new(attacker, square, None, chess_structs::CAPTURE);
+                if self.see(&capture) > 0 {
+                    hanging |= bitboard::bit(square);
+                }
+            }
+        }
+        hanging
+    }
+
+    /// The pieces of both colors attacking `square` when only the squares in `occupied` block sliders,
+    /// so removing a piece from `occupied` brings in the x-ray attackers behind it.
+    fn all_attackers_of(&self, square: Square, occupied: Bitboard) -> Bitboard {
+        (attacks::attackers_with(self, square, Color::White, occupied) | attacks::attackers_with(self, square, Color::Black, occupied)) & occupied
+    }
+}
+
+/// Atomic: the value of the pieces of the opponent a capture blows up, less that of the own pieces, the capturing one included
+fn explosion_value(board: &Board, mv: &Move, color: Color) -> i32 {
+    if !mv.is_capture() {
+        return 0;
+    }
+    let pawns = board.bitboard(Color::White, Kind::Pawn) | board.bitboard(Color::Black, Kind::Pawn);
+    // the pawn taken en passant is not on the target square, but gone all the same
+    let mut value = if mv.is_en_passant() { Kind::Pawn.value() } else { 0 };
+    let mut blast = (attacks::king_attacks(mv.to) & board.occupied & !pawns) | bitboard::bit(mv.from) | bitboard::bit(mv.to);
+    while let Some(square) = bitboard::pop_lsb(&mut blast) {
+        if let Some(piece) = board.piece_at(square) {
+            value += if piece.color == color { -piece.kind.value() } else { piece.kind.value() };
+        }
+    }
+    value
+}
+
+/// Cheapest first, the order in which pieces join an exchange
+const BY_VALUE: [Kind; 6] = [Kind::Pawn, Kind::Knight, Kind::Bishop, Kind::Rook, Kind::Queen, Kind::King];
+
+/// The cheapest of the `attackers`, which are all of color `color`
+fn least_valuable(board: &Board, attackers: Bitboard, color: Color) -> Option<(Square, Kind)> {
+    BY_VALUE.iter()
+        .find_map(|&kind| bitboard::lsb(attackers & board.bitboard(color, kind)).map(|square| (square, kind)))
+}
+
+
+mod tests {
+    use crate::bitboard;
+    use crate::chess_structs;
+    use crate::chess_structs::{Board, Color, Kind, Move, Piece, square};
+
+    fn see(board: &Board, from: (usize, usize), to: (usize, usize)) -> i32 {
+        board.see(&Move::new(square(from.0, from.1), square(to.0, to.1), None, chess_structs::CAPTURE))
+    }
+
+    fn empty_board() -> Board {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(0, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(7, 7), Piece { kind: Kind::King, color: Color::Black });
+        board
+    }
+
+    #[test]
+    fn see_simple_exchanges() {
+        let mut board = empty_board();
+        board.put_piece(square(3, 3), Piece { kind: Kind::Rook, color: Color::White });
+        board.put_piece(square(3, 6), Piece { kind: Kind::Pawn, color: Color::Black });
+        // an undefended pawn
+        assert_eq!(see(&board, (3, 3), (3, 6)), 100);
+
+        // defended by a bishop, the rook is lost for it
+        board.put_piece(square(4, 7), Piece { kind: Kind::Bishop, color: Color::Black });
+        assert_eq!(see(&board, (3, 3), (3, 6)), 100 - 500);
+
+        // a pawn taking a defended knight still wins a knight for a pawn
+        board.put_piece(square(2, 5), Piece { kind: Kind::Knight, color: Color::Black });
+        board.put_piece(square(1, 4), Piece { kind: Kind::Pawn, color: Color::White });
+        assert_eq!(see(&board, (1, 4), (2, 5)), 300 - 100);
+    }
+
+    #[test]
+    fn see_x_rays() {
+        let mut board = empty_board();
+        board.put_piece(square(3, 3), Piece { kind: Kind::Rook, color: Color::White });
+        board.put_piece(square(3, 6), Piece { kind: Kind::Pawn, color: Color::Black });
+        board.put_piece(square(3, 7), Piece { kind: Kind::Rook, color: Color::Black });
+        assert_eq!(see(&board, (3, 3), (3, 6)), 100 - 500);
+
+        // the queen behind the rook takes back: pawn and rook for a rook
+        board.put_piece(square(3, 1), Piece { kind: Kind::Queen, color: Color::White });
+        assert_eq!(see(&board, (3, 3), (3, 6)), 100);
+    }
+
+    #[test]
+    fn see_king_recapture() {
+        let mut board = Board::new(Color::White, None, chess_structs::CASTLING_UNAVAILABLE, chess_structs::NO_CHECKS);
+        board.put_piece(square(0, 0), Piece { kind: Kind::King, color: Color::White });
+        board.put_piece(square(6, 6), Piece { kind: Kind::King, color: Color::Black });
+        board.put_piece(square(6, 5), Piece { kind: Kind::Pawn, color: Color::Black });
+        board.put_piece(square(3, 2), Piece { kind: Kind::Bishop, color: Color::White });
+        // the king takes back, but not when a second white piece covers the square
+        assert_eq!(see(&board, (3, 2), (6, 5)), 100 - 300);
+        board.put_piece(square(6, 0), Piece { kind: Kind::Rook, color: Color::White });
+        assert_eq!(see(&board, (3, 2), (6, 5)), 100);
+    }
+
+    #[test]
+    fn hanging_pieces_test() {
+        let mut board = empty_board();
+        board.put_piece(square(3, 3), Piece { kind: Kind::Knight, color: Color::White });
+        board.put_piece(square(3, 0), Piece { kind: Kind::Rook, color: Color::White });
+        board.put_piece(square(4, 4), Piece { kind: Kind::Pawn, color: Color::Black });
+        board.put_piece(square(1, 1), Piece { kind: Kind::Rook, color: Color::White });
+        board.put_piece(square(2, 0), Piece { kind: Kind::Bishop, color: Color::White });
+        board.put_piece(square(1, 6), Piece { kind: Kind::Rook, color: Color::Black });
+        // the knight is defended, but worth more than the pawn attacking it.
+        // The rooks on the b-file attack each other, only the white one is defended.
+        assert_eq!(board.hanging_pieces(Color::White), bitboard::bit(square(3, 3)));
+        assert_eq!(board.hanging_pieces(Color::Black), bitboard::bit(square(1, 6)));
+    }
+}
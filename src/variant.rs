@@ -0,0 +1,212 @@
+use crate::args;
+use crate::bitboard;
+use crate::chess_structs::{Board, Color, Kind};
+use crate::generator;
+
+/// The rules a game is played by
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Variant {
+    Standard,
+    /// Giving the third check wins
+    ThreeCheck,
+    /// Bringing the king to one of the four center squares wins
+    KingOfTheHill,
+    /// Losing chess: captures are compulsory, the king is an ordinary piece,
+    /// and the side left without pieces or moves wins
+    Antichess,
+    /// Captured pieces change sides and may be dropped back onto the board instead of moving
+    Crazyhouse,
+    /// A capture blows up the capturing piece and every piece but the pawns around the target square,
+    /// and blowing up the enemy king wins
+    Atomic,
+    /// White has a horde of pawns and no king: it wins by checkmate, and loses once all its pieces are captured
+    Horde,
+    /// Both kings race to the eighth rank, and giving check is not allowed. Black drawing level on the move
+    /// right after the white king arrived is a draw.
+    RacingKings,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 8] = [
+        Variant::Standard, Variant::ThreeCheck, Variant::KingOfTheHill, Variant::Antichess, Variant::Crazyhouse, Variant::Atomic, Variant::Horde,
+        Variant::RacingKings
+    ];
+
+    /// The name the variant goes by on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Standard => "standard",
+            Variant::ThreeCheck => "threecheck",
+            Variant::KingOfTheHill => "kingofthehill",
+            Variant::Antichess => "antichess",
+            Variant::Crazyhouse => "crazyhouse",
+            Variant::Atomic => "atomic",
+            Variant::Horde => "horde",
+            Variant::RacingKings => "racingkings",
+        }
+    }
+
+    /// Whether a king may not be left in check
+    pub fn has_checks(self) -> bool {
+        self != Variant::Antichess
+    }
+
+    pub fn from_name(name: &str) -> Option<Variant> {
+        Variant::ALL.iter().copied().find(|variant| variant.name() == name)
+    }
+
+    /// The FEN of the position a game of this variant starts from
+    pub fn start_fen(self) -> &'static str {
+        match self {
+            Variant::Horde => HORDE_FEN,
+            Variant::RacingKings => RACING_KINGS_FEN,
+            _ => args::INITIAL_FEN,
+        }
+    }
+}
+
+/// Horde: 36 white pawns against the usual black army
+pub const HORDE_FEN: &str = "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1";
+
+/// Racing kings: both sides on the first two ranks, without pawns
+pub const RACING_KINGS_FEN: &str = "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1";
+
+/// The number of checks that wins a three-check game
+pub const CHECKS_TO_WIN: u8 = 3;
+
+impl Board {
+    /// The side that has won by a rule of the variant other than checkmate, e.g. by giving the third check.
+    /// The game is over then, and the side to move has no legal moves.
+    pub fn variant_win(&self) -> Option<Color> {
+        match self.variant {
+            Variant::Standard | Variant::Antichess | Variant::Crazyhouse => None,
+            Variant::ThreeCheck => [Color::White, Color::Black].iter()
+                .copied()
+                .find(|color| self.check_counts[color.index()] >= CHECKS_TO_WIN),
+            Variant::KingOfTheHill => [Color::White, Color::Black].iter()
+                .copied()
+                .find(|&color| self.bitboard(color, Kind::King) & bitboard::CENTER != bitboard::EMPTY),
+            Variant::Atomic => [Color::White, Color::Black].iter()
+                .copied()
+                .find(|&color| self.bitboard(color.invert(), Kind::King) == bitboard::EMPTY),
+            Variant::Horde if self.colors[Color::White.index()] == bitboard::EMPTY => Some(Color::Black),
+            Variant::Horde => None,
+            Variant::RacingKings => match (self.reached_goal(Color::White), self.reached_goal(Color::Black)) {
+                (false, true) => Some(Color::Black),
+                // black gets one more move to draw level
+                (true, false) if self.turn == Color::White || !generator::can_reach_goal(self) => Some(Color::White),
+                _ => None
+            },
+        }
+    }
+
+    /// Racing kings: both kings reached the eighth rank, which is a draw
+    pub fn variant_draw(&self) -> bool {
+        self.variant == Variant::RacingKings && self.reached_goal(Color::White) && self.reached_goal(Color::Black)
+    }
+
+    /// Whether the game is over by a rule of the variant, won or drawn
+    pub fn is_variant_end(&self) -> bool {
+        self.variant_win().is_some() || self.variant_draw()
+    }
+
+    /// Racing kings: the king of `color` stands on the eighth rank
+    fn reached_goal(&self, color: Color) -> bool {
+        self.bitboard(color, Kind::King) & bitboard::RANK_8 != bitboard::EMPTY
+    }
+}
+
+
+mod tests {
+    use crate::chess_structs::{Board, Color, Kind, Move};
+    use crate::game_state;
+    use crate::variant::Variant;
+
+    fn play(board: &mut Board, moves: &[&str]) {
+        for name in moves.iter() {
+            let mv: Move = *board.legal_moves().iter().find(|mv| mv.to_string() == *name).unwrap();
+            board.make_move(&mv);
+        }
+    }
+
+    #[test]
+    fn three_check_test() {
+        let mut board = game_state::parse_fen(Color::Black, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::ThreeCheck;
+        play(&mut board, &["e2e4", "d7d5"]);
+
+        // taken back along with the move
+        let before = board;
+        let check = *board.legal_moves().iter().find(|mv| mv.to_string() == "f1b5").unwrap();
+        let undo = board.make_move(&check);
+        assert_eq!(board.check_counts, [1, 0]);
+        board.unmake_move(undo);
+        assert_eq!(board, before);
+
+        play(&mut board, &["f1b5", "c7c6", "b5c6", "b7c6", "d1h5", "a7a6"]);
+        assert_eq!(board.check_counts, [2, 0]);
+        assert_eq!(board.variant_win(), None);
+        play(&mut board, &["h5f7"]);
+        assert_eq!(board.variant_win(), Some(Color::White));
+        assert!(board.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn king_of_the_hill_test() {
+        let mut board = game_state::parse_fen(Color::Black, "4k3/8/8/8/8/2K5/8/8 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::KingOfTheHill;
+        assert_eq!(board.variant_win(), None);
+        play(&mut board, &["c3d4"]);
+        assert_eq!(board.variant_win(), Some(Color::White));
+        assert!(board.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn horde_test() {
+        let board = game_state::parse_fen(Color::Black, Variant::Horde.start_fen()).ok().unwrap().board_state;
+        assert_eq!(board.bitboard(Color::White, Kind::Pawn).count_ones(), 36);
+        assert_eq!(board.colors[Color::White.index()], board.bitboard(Color::White, Kind::Pawn));
+
+        // a pawn on the first rank may push two squares, without leaving an en passant square
+        let mut board = game_state::parse_fen(Color::Black, "4k3/8/8/8/8/8/8/4P3 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::Horde;
+        let moves: Vec<String> = board.legal_moves().iter().map(|mv| mv.to_string()).collect();
+        assert_eq!(moves, vec!["e1e2", "e1e3"]);
+        play(&mut board, &["e1e3"]);
+        assert_eq!(board.en_passant, None);
+        assert_eq!(board.variant_win(), None);
+
+        play(&mut board, &["e8e7", "e3e4", "e7e6", "e4e5", "e6e5"]);
+        assert_eq!(board.variant_win(), Some(Color::Black));
+        assert!(board.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn racing_kings_test() {
+        // neither moving the rook in front of the king nor onto its rank is allowed
+        let mut board = game_state::parse_fen(Color::Black, "8/8/8/8/8/k7/8/1R5K w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::RacingKings;
+        let moves: Vec<String> = board.legal_moves().iter().map(|mv| mv.to_string()).collect();
+        assert!(!moves.contains(&"b1a1".to_string()));
+        assert!(!moves.contains(&"b1b3".to_string()));
+        assert!(moves.contains(&"b1b2".to_string()));
+
+        // the black king is too far behind to draw level
+        let mut board = game_state::parse_fen(Color::Black, "8/2K5/8/8/8/8/5k2/8 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::RacingKings;
+        play(&mut board, &["c7c8"]);
+        assert_eq!(board.variant_win(), Some(Color::White));
+        assert!(board.legal_moves().is_empty());
+
+        // here it is not, and reaching the eighth rank right after the white king draws
+        let mut board = game_state::parse_fen(Color::Black, "8/2K2k2/8/8/8/8/8/8 w - - 0 1").ok().unwrap().board_state;
+        board.variant = Variant::RacingKings;
+        play(&mut board, &["c7c8"]);
+        assert_eq!(board.variant_win(), None);
+        assert!(!board.variant_draw());
+        play(&mut board, &["f7f8"]);
+        assert_eq!(board.variant_win(), None);
+        assert!(board.variant_draw());
+        assert!(board.legal_moves().is_empty());
+    }
+}